@@ -0,0 +1,13 @@
+fn main() {
+    // Enables the native BigInt API when `rusty_jsc_sys` found it in the
+    // linked JavaScriptCore, see `sys/build.rs`.
+    println!("cargo:rustc-check-cfg=cfg(jsc_bigint_api)");
+    if std::env::var_os("DEP_JAVASCRIPTCORE_BIGINT_API").is_some() {
+        println!("cargo:rustc-cfg=jsc_bigint_api");
+    }
+    // Same for the execution time limit, a private API on macOS.
+    println!("cargo:rustc-check-cfg=cfg(jsc_execution_time_limit)");
+    if std::env::var_os("DEP_JAVASCRIPTCORE_EXECUTION_TIME_LIMIT").is_some() {
+        println!("cargo:rustc-cfg=jsc_execution_time_limit");
+    }
+}
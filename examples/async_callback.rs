@@ -0,0 +1,65 @@
+use rusty_jsc::{callback, JSContext, JSError, JSValue};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// A future completing after a delay, measured on another thread.
+struct Sleep {
+    state: Arc<Mutex<(bool, Option<Waker>)>>,
+}
+
+fn sleep(duration: Duration) -> Sleep {
+    let state = Arc::new(Mutex::new((false, None::<Waker>)));
+    let thread_state = state.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        let mut state = thread_state.lock().unwrap();
+        state.0 = true;
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    });
+    Sleep { state }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 {
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[callback]
+async fn greet(_ctx: JSContext, name: String, delay: u32) -> Result<String, JSError> {
+    if name.is_empty() {
+        return Err(JSError::type_error("expected a name"));
+    }
+    sleep(Duration::from_millis(delay as u64)).await;
+    Ok(format!("Hello, {name}!"))
+}
+
+fn main() {
+    let mut context = JSContext::default();
+    let greet = JSValue::callback(&context, Some(greet));
+    let mut global = context.get_global_object();
+    global.set_property(&context, "greet", greet).unwrap();
+
+    context
+        .evaluate_script(
+            "greet('async world', 100).then((greeting) => globalThis.greeting = greeting)",
+            1,
+        )
+        .unwrap();
+    context.run_tasks();
+
+    let greeting = global.get_property(&context, "greeting").unwrap();
+    println!("{}", greeting.to_js_string(&context).unwrap());
+}
@@ -0,0 +1,40 @@
+use rusty_jsc::{constructor, JSClass, JSContext, JSObject, JSValue};
+
+thread_local! {
+    static POINT: JSClass = JSClass::create("Point", Some(point));
+}
+
+/// Creates a point from its two coordinates.
+#[constructor]
+pub(crate) fn point(
+    ctx: JSContext,
+    constructor: JSObject,
+    args: &[JSValue],
+) -> Result<JSObject, JSValue> {
+    if args.len() != 2 {
+        return Err(JSValue::string(&ctx, "Point expects two coordinates"));
+    }
+    let mut instance = POINT.with(|class| class.make_instance(&ctx, &constructor))?;
+    instance.set_property(&ctx, "x", args[0].clone())?;
+    instance.set_property(&ctx, "y", args[1].clone())?;
+    Ok(instance.into())
+}
+
+fn main() {
+    let mut context = JSContext::default();
+    let point = POINT.with(|class| class.make_object(&context));
+
+    let mut global = context.get_global_object();
+    global
+        .set_property(&context, "Point", point.into())
+        .unwrap();
+    let result = context.evaluate_script(
+        "Point.prototype = { norm() { return Math.hypot(this.x, this.y); } };
+         new Point(3, 4).norm()",
+        1,
+    );
+    match result {
+        Ok(value) => println!("{}", value.to_js_string(&context).unwrap()),
+        Err(e) => println!("Uncaught: {}", e.to_js_string(&context).unwrap()),
+    }
+}
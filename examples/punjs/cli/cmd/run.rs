@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use rusty_jsc::{JSContext, JSValue};
+use rusty_jsc::{JSContext, JSValue, StdioSink};
 use rusty_jsc_macros::callback;
 use std::fs;
 use std::path::PathBuf;
@@ -17,6 +17,10 @@ pub fn run(input: PathBuf) -> Result<()> {
 }
 
 fn setup_prelude(context: &JSContext) {
+    // console
+    context
+        .install_console(StdioSink)
+        .expect("failed to install console");
     let require_fn = JSValue::callback(&context, Some(require));
     // require()
     let mut global = context.get_global_object();
@@ -13,6 +13,15 @@ fn get_name(func_argument: &FnArg) -> Ident {
     }
 }
 
+fn get_type(func_argument: &FnArg) -> syn::Type {
+    match func_argument {
+        FnArg::Typed(fn_type) => (*fn_type.ty).clone(),
+        _ => {
+            panic!("Not supported function argument")
+        }
+    }
+}
+
 fn get_name_pat(func_argument: &Pat) -> Ident {
     match func_argument {
         Pat::Ident(ident) => ident.ident.clone(),
@@ -23,6 +32,7 @@ fn get_name_pat(func_argument: &Pat) -> Ident {
     }
 }
 
+#[proc_macro_error]
 #[proc_macro_attribute]
 pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let func = syn::parse::<syn::ItemFn>(item).expect("expected a function");
@@ -45,6 +55,10 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
         quote! { #target_func_name }
     };
 
+    if func.sig.asyncness.is_some() {
+        return async_callback(&func, target_func, target_func_name);
+    }
+
     let all_inputs = func.sig.inputs.iter().collect::<Vec<_>>();
     assert_eq!(all_inputs.len(), 4);
     let context_var_name = get_name(all_inputs.get(0).unwrap());
@@ -66,8 +80,7 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
             rusty_jsc::private::JSValueMakeUndefined(__base_ctx)
         },
         _ => quote! {
-            //let res: Result<JSValue, JSValue> = todo!();
-            let res: Result<JSValue, JSValue> = #target_func_name(
+            let res: Result<rusty_jsc::JSValue, rusty_jsc::JSValue> = #target_func_name(
                 #context_var_name,
                 #function_var_name,
                 #this_var_name,
@@ -77,13 +90,14 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 Ok(res) => res.into(),
                 Err(err) => {
                     *__exception = err.into();
-                    let ctx2 = rusty_jsc::JSContext::from(__base_ctx);
                     rusty_jsc::private::JSValueMakeUndefined(__base_ctx)
                 }
             }
         },
     };
 
+    // A panic must not unwind into JavaScriptCore, so it's caught and handled
+    // according to the panic policy of the context.
     let result = quote! {
         unsafe extern "C" fn #name(
             __base_ctx: rusty_jsc::private::JSContextRef,
@@ -93,19 +107,28 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
             __arguments: *const rusty_jsc::private::JSValueRef,
             mut __exception: *mut rusty_jsc::private::JSValueRef,
         ) -> rusty_jsc::private::JSValueRef {
-            let #context_var_name = rusty_jsc::JSContext::from(__base_ctx);
-            let #function_var_name: rusty_jsc::JSObject= __function.into();
-            let #this_var_name: rusty_jsc::JSObject = __this_object.into();
-            let #args_var_name = if __argument_count == 0 {
-                vec![]
-            }
-            else {
-                let __args_refs_slice = unsafe { std::slice::from_raw_parts(__arguments, __argument_count as _) };
-                __args_refs_slice.iter().map(|r| (*r).into()).collect::<Vec<_>>()
-            };
-            let #args_var_name: &[JSValue] = &#args_var_name;
+            let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let #context_var_name = rusty_jsc::JSContext::from(__base_ctx);
+                let #function_var_name: rusty_jsc::JSObject = __function.into();
+                let #this_var_name: rusty_jsc::JSObject = __this_object.into();
+                let #args_var_name = if __argument_count == 0 {
+                    vec![]
+                }
+                else {
+                    let __args_refs_slice = unsafe { std::slice::from_raw_parts(__arguments, __argument_count as _) };
+                    __args_refs_slice.iter().map(|r| (*r).into()).collect::<Vec<_>>()
+                };
+                let #args_var_name: &[rusty_jsc::JSValue] = &#args_var_name;
 
-            #block_call
+                #block_call
+            }));
+            match __result {
+                Ok(value) => value,
+                Err(payload) => {
+                    rusty_jsc::private::handle_callback_panic(__base_ctx, payload, __exception);
+                    rusty_jsc::private::JSValueMakeUndefined(__base_ctx)
+                }
+            }
         }
     };
     let new_func = result.into();
@@ -116,7 +139,6 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
     new_func.sig.generics = func.sig.generics;
     new_func.sig.constness = func.sig.constness;
     new_func.sig.variadic = func.sig.variadic;
-    new_func.sig.asyncness = func.sig.asyncness;
 
     quote! {
         #[inline]
@@ -126,6 +148,158 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Checks if a type holds a `JSValue` or a `JSObject` outside of a
+/// `Persistent` handle.
+fn holds_unprotected_value(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.iter().any(|segment| {
+            if segment.ident == "Persistent" {
+                return false;
+            }
+            if segment.ident == "JSValue" || segment.ident == "JSObject" {
+                return true;
+            }
+            match &segment.arguments {
+                syn::PathArguments::AngleBracketed(arguments) => {
+                    arguments.args.iter().any(|argument| match argument {
+                        syn::GenericArgument::Type(ty) => holds_unprotected_value(ty),
+                        _ => false,
+                    })
+                }
+                _ => false,
+            }
+        }),
+        syn::Type::Reference(reference) => holds_unprotected_value(&reference.elem),
+        syn::Type::Slice(slice) => holds_unprotected_value(&slice.elem),
+        syn::Type::Array(array) => holds_unprotected_value(&array.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().any(holds_unprotected_value),
+        syn::Type::Paren(paren) => holds_unprotected_value(&paren.elem),
+        syn::Type::Group(group) => holds_unprotected_value(&group.elem),
+        _ => false,
+    }
+}
+
+/// Generates the function callback of an `async fn`.
+///
+/// The function takes the `JSContext` and then the arguments, converted with
+/// `FromJSValue` (a missing argument is `undefined`), and returns a
+/// `Result<T, E>` where `T: IntoJSValue` and `E: IntoJSValue`, e.g. a
+/// `JSError`, or nothing. The generated callback returns a promise settled
+/// with the result of the future, which runs on the executor of the context.
+///
+/// The garbage collector doesn't see the values held by the future, so the
+/// arguments can't be `JSValue`s or `JSObject`s: take them as
+/// `Persistent<JSValue>` or `Persistent<JSObject>` instead.
+fn async_callback(
+    func: &syn::ItemFn,
+    target_func: syn::ItemFn,
+    target_func_name: impl quote::ToTokens,
+) -> TokenStream {
+    let name = &func.sig.ident;
+    let inputs = func.sig.inputs.iter().collect::<Vec<_>>();
+    let context_var_name = match inputs.first() {
+        Some(input) => get_name(input),
+        None => panic!("expected a context parameter"),
+    };
+    let arg_names = inputs[1..].iter().map(|input| get_name(input)).collect::<Vec<_>>();
+    let arg_types = inputs[1..].iter().map(|input| get_type(input)).collect::<Vec<_>>();
+    for (input, ty) in inputs[1..].iter().zip(&arg_types) {
+        if holds_unprotected_value(ty) {
+            emit_error! {
+                input,
+                "the arguments of an async callback are kept across awaits, where they aren't protected from garbage collection";
+                help = "take a `Persistent<JSValue>` or a `Persistent<JSObject>` instead"
+            }
+        }
+    }
+    let arg_indexes = 0..arg_names.len();
+
+    let result_handling = match func.sig.output {
+        syn::ReturnType::Default => quote! {
+            #target_func_name(#context_var_name, #(#arg_names),*).await;
+            Ok(rusty_jsc::JSValue::undefined(&__future_ctx))
+        },
+        _ => quote! {
+            match #target_func_name(#context_var_name, #(#arg_names),*).await {
+                Ok(__value) => rusty_jsc::IntoJSValue::into_js_value(__value, &__future_ctx),
+                Err(__error) => Err(
+                    match rusty_jsc::IntoJSValue::into_js_value(__error, &__future_ctx) {
+                        Ok(__error) => rusty_jsc::JSException::from(__error),
+                        Err(__exception) => __exception,
+                    },
+                ),
+            }
+        },
+    };
+
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+    let generics = &func.sig.generics;
+    let where_clause = &func.sig.generics.where_clause;
+    quote! {
+        #[inline]
+        #target_func
+
+        #(#attrs)*
+        #vis unsafe extern "C" fn #name #generics(
+            __base_ctx: rusty_jsc::private::JSContextRef,
+            __function: rusty_jsc::private::JSObjectRef,
+            __this_object: rusty_jsc::private::JSObjectRef,
+            __argument_count: rusty_jsc::private::size_t,
+            __arguments: *const rusty_jsc::private::JSValueRef,
+            __exception: *mut rusty_jsc::private::JSValueRef,
+        ) -> rusty_jsc::private::JSValueRef #where_clause {
+            let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let __ctx = rusty_jsc::JSContext::from(__base_ctx);
+                let __args: &[rusty_jsc::private::JSValueRef] = if __argument_count == 0 {
+                    &[]
+                } else {
+                    std::slice::from_raw_parts(__arguments, __argument_count as _)
+                };
+                let __arg = |index: usize| {
+                    __args
+                        .get(index)
+                        .map_or_else(|| rusty_jsc::JSValue::undefined(&__ctx), |r| rusty_jsc::JSValue::from(*r))
+                };
+                // The arguments are converted before the future is spawned,
+                // while they are still referenced by the caller. They can't be
+                // unprotected JavaScript values, which the future would keep
+                // across awaits.
+                let __converted = (|| -> Result<_, rusty_jsc::JSException> {
+                    Ok((#(<#arg_types as rusty_jsc::FromJSValue>::from_js_value(&__ctx, &__arg(#arg_indexes))?,)*))
+                })();
+                let #context_var_name = rusty_jsc::JSContext::from(__base_ctx);
+                let __future_ctx = rusty_jsc::JSContext::from(__base_ctx);
+                rusty_jsc::private::spawn_promise(&__ctx, async move {
+                    let (#(#arg_names,)*) = __converted?;
+                    #result_handling
+                })
+            }));
+            match __result {
+                Ok(value) => value,
+                Err(payload) => {
+                    rusty_jsc::private::handle_callback_panic(__base_ctx, payload, __exception);
+                    rusty_jsc::private::JSValueMakeUndefined(__base_ctx)
+                }
+            }
+        }
+    }
+    .into()
+}
+
+/// Turns a function into a `JSObjectCallAsConstructorCallback`, to be used
+/// with `JSClass::create`.
+///
+/// The function takes up to four parameters, in this order:
+///
+/// - the `JSContext` of the call,
+/// - the constructor `new` was applied to, as a `JSObject`; this is the callee
+///   itself, not `new.target`, even when constructing a subclass,
+/// - the arguments as a `&[JSValue]`,
+/// - an exception as a `&mut Option<JSValue>`, setting it throws the value.
+///
+/// It returns a `Result<JSObject, JSValue>` with the new instance or the
+/// value to throw.
 #[proc_macro_error]
 #[proc_macro_attribute]
 pub fn constructor(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -138,56 +312,294 @@ pub fn constructor(_attr: TokenStream, item: TokenStream) -> TokenStream {
     };
     let target_name = &target_func.sig.ident;
 
-    let mut inputs_adaptation = vec![];
-    let mut inputs_number = func.sig.inputs.len();
-
-    if inputs_number == 4 {
-        emit_error! { func, "Fourth exception parameter is unimplemented" }
-        inputs_number -= 1;
+    let inputs = func.sig.inputs.iter().collect::<Vec<_>>();
+    if inputs.len() > 4 {
+        emit_error! { func, "expected at most 4 parameters: context, constructor, arguments and exception" }
     }
-    if inputs_number == 3 {
+    if let syn::ReturnType::Default = func.sig.output {
+        emit_error! { func, "expected a `Result<JSObject, JSValue>` return type" }
+    }
+
+    let mut inputs_adaptation = vec![];
+    let mut call_arguments = vec![];
+    if let Some(input) = inputs.first() {
+        let name = get_name(input);
         inputs_adaptation.push(quote! {
-            let arguments = (0.._argument_count as isize)
-                .map(|arg_index| JSValue::from(_arguments.offset(arg_index).read()))
-                .collect();
+            let #name = rusty_jsc::JSContext::from(__base_ctx);
         });
-        inputs_number -= 1;
+        call_arguments.push(quote! { #name });
     }
-    if inputs_number == 2 {
+    if let Some(input) = inputs.get(1) {
+        let name = get_name(input);
         inputs_adaptation.push(quote! {
-            let constructor = JSObject::from(_constructor);
+            let #name: rusty_jsc::JSObject = __constructor.into();
         });
-        inputs_number -= 1;
+        call_arguments.push(quote! { #name });
     }
-    if inputs_number == 1 {
+    if let Some(input) = inputs.get(2) {
+        let name = get_name(input);
         inputs_adaptation.push(quote! {
-            let context = JSContext::from(_ctx);
+            let #name = if __argument_count == 0 {
+                vec![]
+            } else {
+                std::slice::from_raw_parts(__arguments, __argument_count as _)
+                    .iter()
+                    .map(|r| rusty_jsc::JSValue::from(*r))
+                    .collect::<Vec<_>>()
+            };
         });
+        call_arguments.push(quote! { &#name });
     }
-
-    let function_call = match func.sig.inputs.len() {
-        1 => quote! { #target_name(context) },
-        2 => quote! { #target_name(context, constructor) },
-        3 => quote! { #target_name(context, constructor, arguments) },
-        _ => quote! { #target_name() },
+    let exception_check = if let Some(input) = inputs.get(3) {
+        let name = get_name(input);
+        inputs_adaptation.push(quote! {
+            let mut #name: Option<rusty_jsc::JSValue> = None;
+        });
+        call_arguments.push(quote! { &mut #name });
+        quote! {
+            if let Some(err) = #name {
+                *__exception = err.into();
+                return std::ptr::null_mut();
+            }
+        }
+    } else {
+        quote! {}
     };
 
+    let attrs = &func.attrs;
+    let vis = &func.vis;
     let result = quote! {
-        use rusty_jsc_sys::*;
-
+        #[inline]
         #target_func
 
-        unsafe extern "C" fn #name(
-            _ctx: JSContextRef,
-            _constructor: JSObjectRef,
-            _argument_count: size_t,
-            _arguments: *const JSValueRef,
-            _exception: *mut JSValueRef,
-        ) -> JSObjectRef {
-            #(#inputs_adaptation)*
-            #function_call;
-            _constructor
+        #(#attrs)*
+        #vis unsafe extern "C" fn #name(
+            __base_ctx: rusty_jsc::private::JSContextRef,
+            __constructor: rusty_jsc::private::JSObjectRef,
+            __argument_count: rusty_jsc::private::size_t,
+            __arguments: *const rusty_jsc::private::JSValueRef,
+            __exception: *mut rusty_jsc::private::JSValueRef,
+        ) -> rusty_jsc::private::JSObjectRef {
+            let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                #(#inputs_adaptation)*
+                let res = #target_name(#(#call_arguments),*);
+                #exception_check
+                match res {
+                    Ok(object) => {
+                        let object: rusty_jsc::JSObject = object.into();
+                        object.into()
+                    }
+                    Err(err) => {
+                        let err: rusty_jsc::JSValue = err.into();
+                        *__exception = err.into();
+                        std::ptr::null_mut()
+                    }
+                }
+            }));
+            match __result {
+                Ok(object) => object,
+                Err(payload) => {
+                    rusty_jsc::private::handle_constructor_panic(__base_ctx, payload, __exception);
+                    std::ptr::null_mut()
+                }
+            }
         }
     };
     result.into()
 }
+
+/// Turns a function into a native hook of `JSClassHooks`, to be used with
+/// `JSClass::create_with_hooks`.
+///
+/// The attribute names the hook, and the function takes the `JSContext`, the
+/// `JSObject` whose property is accessed and, except for
+/// `get_property_names`, the name of the property as a `JSString`:
+///
+/// - `has_property` returns a `bool`, `false` continuing the lookup with the
+///   regular properties and the prototype chain,
+/// - `get_property` returns a `Result<Option<JSValue>, JSValue>`, `None`
+///   continuing the lookup,
+/// - `set_property` also takes the new `JSValue` and returns a
+///   `Result<bool, JSValue>`, `false` setting the property as usual,
+/// - `delete_property` returns a `Result<bool, JSValue>`, `false` deleting
+///   the property as usual,
+/// - `get_property_names` returns the names to add to the enumerated
+///   properties, as any iterator of values convertible into a `JSString`.
+///
+/// Returning an error throws it. A panic is handled according to the panic
+/// policy of the context; `has_property` and `get_property_names` can't
+/// throw, so they log it instead.
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn class_hook(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = syn::parse::<syn::ItemFn>(item).expect("expected a function");
+    let kind = match syn::parse::<Ident>(attr) {
+        Ok(kind) => kind,
+        Err(_) => {
+            emit_error! { func, "expected the name of the hook, e.g. `#[class_hook(get_property)]`" }
+            return quote! { #func }.into();
+        }
+    };
+    let name = &func.sig.ident;
+    let target_func = {
+        let mut func = func.clone();
+        func.sig.ident = quote::format_ident!("{}_hook", name);
+        func
+    };
+    let target_name = &target_func.sig.ident;
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+
+    let expected_inputs = if kind == "get_property_names" {
+        2
+    } else if kind == "set_property" {
+        4
+    } else {
+        3
+    };
+    if func.sig.inputs.len() != expected_inputs {
+        emit_error! { func, "a `{}` hook takes {} parameters", kind, expected_inputs }
+    }
+
+    let inputs = quote! {
+        let __ctx = rusty_jsc::JSContext::from(__base_ctx);
+        let __object: rusty_jsc::JSObject = __object.into();
+    };
+    // The property name is borrowed from JavaScriptCore.
+    let property_name = quote! {
+        let __property_name = rusty_jsc::JSString::from(
+            rusty_jsc::private::JSStringRetain(__property_name),
+        );
+    };
+    let hook = if kind == "has_property" {
+        quote! {
+            #vis unsafe extern "C" fn #name(
+                __base_ctx: rusty_jsc::private::JSContextRef,
+                __object: rusty_jsc::private::JSObjectRef,
+                __property_name: rusty_jsc::private::JSStringRef,
+            ) -> bool {
+                let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    #inputs
+                    #property_name
+                    #target_name(__ctx, __object, __property_name)
+                }));
+                match __result {
+                    Ok(found) => found,
+                    Err(payload) => {
+                        rusty_jsc::private::handle_hook_panic(__base_ctx, payload);
+                        false
+                    }
+                }
+            }
+        }
+    } else if kind == "get_property" {
+        quote! {
+            #vis unsafe extern "C" fn #name(
+                __base_ctx: rusty_jsc::private::JSContextRef,
+                __object: rusty_jsc::private::JSObjectRef,
+                __property_name: rusty_jsc::private::JSStringRef,
+                __exception: *mut rusty_jsc::private::JSValueRef,
+            ) -> rusty_jsc::private::JSValueRef {
+                let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    #inputs
+                    #property_name
+                    let res: Result<Option<rusty_jsc::JSValue>, rusty_jsc::JSValue> =
+                        #target_name(__ctx, __object, __property_name);
+                    match res {
+                        Ok(Some(value)) => value.into(),
+                        Ok(None) => std::ptr::null(),
+                        Err(err) => {
+                            *__exception = err.into();
+                            std::ptr::null()
+                        }
+                    }
+                }));
+                match __result {
+                    Ok(value) => value,
+                    Err(payload) => {
+                        rusty_jsc::private::handle_callback_panic(__base_ctx, payload, __exception);
+                        std::ptr::null()
+                    }
+                }
+            }
+        }
+    } else if kind == "set_property" || kind == "delete_property" {
+        let (value_param, value_input, value_arg) = if kind == "set_property" {
+            (
+                quote! { __value: rusty_jsc::private::JSValueRef, },
+                quote! { let __value = rusty_jsc::JSValue::from(__value); },
+                quote! { , __value },
+            )
+        } else {
+            (quote! {}, quote! {}, quote! {})
+        };
+        quote! {
+            #vis unsafe extern "C" fn #name(
+                __base_ctx: rusty_jsc::private::JSContextRef,
+                __object: rusty_jsc::private::JSObjectRef,
+                __property_name: rusty_jsc::private::JSStringRef,
+                #value_param
+                __exception: *mut rusty_jsc::private::JSValueRef,
+            ) -> bool {
+                let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    #inputs
+                    #property_name
+                    #value_input
+                    let res: Result<bool, rusty_jsc::JSValue> =
+                        #target_name(__ctx, __object, __property_name #value_arg);
+                    match res {
+                        Ok(handled) => handled,
+                        Err(err) => {
+                            *__exception = err.into();
+                            true
+                        }
+                    }
+                }));
+                match __result {
+                    Ok(handled) => handled,
+                    Err(payload) => {
+                        rusty_jsc::private::handle_callback_panic(__base_ctx, payload, __exception);
+                        true
+                    }
+                }
+            }
+        }
+    } else if kind == "get_property_names" {
+        quote! {
+            #vis unsafe extern "C" fn #name(
+                __base_ctx: rusty_jsc::private::JSContextRef,
+                __object: rusty_jsc::private::JSObjectRef,
+                __accumulator: rusty_jsc::private::JSPropertyNameAccumulatorRef,
+            ) {
+                let __result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    #inputs
+                    for __name in #target_name(__ctx, __object) {
+                        let __name: rusty_jsc::JSString = __name.into();
+                        rusty_jsc::private::JSPropertyNameAccumulatorAddName(
+                            __accumulator,
+                            __name.inner,
+                        );
+                    }
+                }));
+                if let Err(payload) = __result {
+                    rusty_jsc::private::handle_hook_panic(__base_ctx, payload);
+                }
+            }
+        }
+    } else {
+        emit_error! {
+            kind,
+            "unknown hook, expected `has_property`, `get_property`, `set_property`, `delete_property` or `get_property_names`"
+        }
+        return quote! { #func }.into();
+    };
+
+    quote! {
+        #[inline]
+        #target_func
+
+        #(#attrs)*
+        #hook
+    }
+    .into()
+}
@@ -0,0 +1,331 @@
+//! BigInt support.
+//!
+//! When the linked JavaScriptCore provides the `JSBigInt*` C API (see the
+//! `jsc_bigint_api` cfg set by the build script), BigInts are created and
+//! recognized natively. Otherwise, the same operations fall back to calling
+//! the `BigInt` global function from JavaScript.
+//!
+//! Extracting a BigInt into a Rust integer is always exact: it goes through
+//! the decimal representation of the BigInt and fails if the value doesn't
+//! fit in the target type.
+
+use crate::{
+    convert::conversion_error, FromJSValue, IntoJSValue, JSContext, JSException, JSObject,
+    JSString, JSValue, TypedArrayType,
+};
+use rusty_jsc_sys::*;
+use std::str::FromStr;
+
+/// The largest integer that a JavaScript number represents exactly.
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+impl JSValue {
+    /// Creates a `bigint` value from an `i64`.
+    ///
+    /// The `bigint` functions give the same results with the native API and
+    /// with the fallback calling `BigInt`:
+    ///
+    /// ```rust
+    /// use rusty_jsc::{IntoJSValue, JSContext, JSValue, JSValueKind};
+    ///
+    /// let context = JSContext::default();
+    /// let bigint = JSValue::bigint_from_i64(&context, -5).unwrap();
+    /// assert!(bigint.is_bigint(&context));
+    /// assert_eq!(bigint.kind(&context), JSValueKind::BigInt);
+    /// assert_eq!(bigint.bigint_to_i64(&context).unwrap(), -5);
+    /// assert!(bigint.bigint_to_u64(&context).is_err());
+    /// let parsed = JSValue::bigint_from_str(&context, "0x10").unwrap();
+    /// assert_eq!(parsed.bigint_to_u64(&context).unwrap(), 16);
+    /// assert!(JSValue::bigint_from_str(&context, "1.5").is_err());
+    ///
+    /// // The integer types convert to numbers when the value is a safe
+    /// // integer, and to BigInts otherwise.
+    /// let small = 42i64.into_js_value(&context).unwrap();
+    /// assert_eq!(small.kind(&context), JSValueKind::Number);
+    /// let large = u64::MAX.into_js_value(&context).unwrap();
+    /// assert_eq!(large.kind(&context), JSValueKind::BigInt);
+    /// assert_eq!(large.bigint_to_u64(&context).unwrap(), u64::MAX);
+    /// ```
+    pub fn bigint_from_i64(context: &JSContext, value: i64) -> Result<JSValue, JSException> {
+        #[cfg(jsc_bigint_api)]
+        {
+            let mut exception: JSValueRef = std::ptr::null_mut();
+            let bigint = unsafe { JSBigIntCreateWithInt64(context.inner, value, &mut exception) };
+            if !exception.is_null() {
+                return Err(JSException::from(exception));
+            }
+            Ok(JSValue::from(bigint))
+        }
+        #[cfg(not(jsc_bigint_api))]
+        Self::bigint_from_str(context, &value.to_string())
+    }
+
+    /// Creates a `bigint` value from an `u64`.
+    pub fn bigint_from_u64(context: &JSContext, value: u64) -> Result<JSValue, JSException> {
+        #[cfg(jsc_bigint_api)]
+        {
+            let mut exception: JSValueRef = std::ptr::null_mut();
+            let bigint = unsafe { JSBigIntCreateWithUInt64(context.inner, value, &mut exception) };
+            if !exception.is_null() {
+                return Err(JSException::from(exception));
+            }
+            Ok(JSValue::from(bigint))
+        }
+        #[cfg(not(jsc_bigint_api))]
+        Self::bigint_from_str(context, &value.to_string())
+    }
+
+    /// Creates a `bigint` value from an `i128`.
+    pub fn bigint_from_i128(context: &JSContext, value: i128) -> Result<JSValue, JSException> {
+        Self::bigint_from_str(context, &value.to_string())
+    }
+
+    /// Creates a `bigint` value from an `u128`.
+    pub fn bigint_from_u128(context: &JSContext, value: u128) -> Result<JSValue, JSException> {
+        Self::bigint_from_str(context, &value.to_string())
+    }
+
+    /// Creates a `bigint` value by parsing a string, like `BigInt(string)`.
+    ///
+    /// The string is a decimal integer, or a hexadecimal, octal or binary
+    /// integer with a `0x`, `0o` or `0b` prefix. A `SyntaxError` is returned
+    /// if the string isn't a valid integer.
+    pub fn bigint_from_str(context: &JSContext, value: &str) -> Result<JSValue, JSException> {
+        let string: JSString = value.into();
+        #[cfg(jsc_bigint_api)]
+        {
+            let mut exception: JSValueRef = std::ptr::null_mut();
+            let bigint =
+                unsafe { JSBigIntCreateWithString(context.inner, string.inner, &mut exception) };
+            if !exception.is_null() {
+                return Err(JSException::from(exception));
+            }
+            Ok(JSValue::from(bigint))
+        }
+        #[cfg(not(jsc_bigint_api))]
+        {
+            let bigint = context
+                .get_global_object()
+                .get_property(context, "BigInt")?
+                .to_object(context)?;
+            bigint.call_as_function(context, None, &[JSValue::string(context, string)])
+        }
+    }
+
+    /// Checks if this value is `bigint`.
+    pub fn is_bigint(&self, context: &JSContext) -> bool {
+        #[cfg(jsc_bigint_api)]
+        {
+            unsafe { JSValueIsBigInt(context.inner, self.inner) }
+        }
+        #[cfg(not(jsc_bigint_api))]
+        {
+            self.primitive_kind(context) == Some(crate::JSValueKind::BigInt)
+        }
+    }
+
+    /// Gets this `bigint` value as an `i64`, failing if it doesn't fit.
+    pub fn bigint_to_i64(&self, context: &JSContext) -> Result<i64, JSException> {
+        self.parse_bigint(context)
+    }
+
+    /// Gets this `bigint` value as an `u64`, failing if it doesn't fit.
+    pub fn bigint_to_u64(&self, context: &JSContext) -> Result<u64, JSException> {
+        self.parse_bigint(context)
+    }
+
+    /// Gets this `bigint` value as an `i128`, failing if it doesn't fit.
+    pub fn bigint_to_i128(&self, context: &JSContext) -> Result<i128, JSException> {
+        self.parse_bigint(context)
+    }
+
+    /// Gets this `bigint` value as an `u128`, failing if it doesn't fit.
+    pub fn bigint_to_u128(&self, context: &JSContext) -> Result<u128, JSException> {
+        self.parse_bigint(context)
+    }
+
+    fn parse_bigint<T: FromStr>(&self, context: &JSContext) -> Result<T, JSException> {
+        if !self.is_bigint(context) {
+            return Err(conversion_error(context, "value is not a BigInt"));
+        }
+        let digits = self.to_js_string(context)?.to_string_lossy();
+        digits.parse::<T>().map_err(|_| {
+            conversion_error(
+                context,
+                format!(
+                    "{digits} is out of the range of {}",
+                    std::any::type_name::<T>()
+                ),
+            )
+        })
+    }
+}
+
+impl<T> JSObject<T> {
+    /// Creates a typed array of the given type and length, filled with
+    /// zeros.
+    pub fn new_typed_array(
+        context: &JSContext,
+        typed_array_type: TypedArrayType,
+        length: usize,
+    ) -> Result<JSObject, JSException> {
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let result = unsafe {
+            JSObjectMakeTypedArray(
+                context.inner,
+                typed_array_type.to_raw(),
+                length as _,
+                &mut exception,
+            )
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(JSObject::from(result))
+    }
+
+    /// Returns the element type of a typed array, or `None` if the object
+    /// isn't a typed array.
+    pub fn typed_array_type(&self, context: &JSContext) -> Option<TypedArrayType> {
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let raw = unsafe { JSValueGetTypedArrayType(context.inner, self.inner, &mut exception) };
+        TypedArrayType::from_raw(raw)
+    }
+
+    /// Creates a `BigInt64Array` holding a copy of `values`.
+    pub fn new_bigint64_array(
+        context: &JSContext,
+        values: &[i64],
+    ) -> Result<JSObject, JSException> {
+        let bytes = values.iter().flat_map(|value| value.to_ne_bytes());
+        Self::new_typed_array_with(context, TypedArrayType::BigInt64Array, values.len(), bytes)
+    }
+
+    /// Creates a `BigUint64Array` holding a copy of `values`.
+    pub fn new_biguint64_array(
+        context: &JSContext,
+        values: &[u64],
+    ) -> Result<JSObject, JSException> {
+        let bytes = values.iter().flat_map(|value| value.to_ne_bytes());
+        Self::new_typed_array_with(context, TypedArrayType::BigUint64Array, values.len(), bytes)
+    }
+
+    fn new_typed_array_with(
+        context: &JSContext,
+        typed_array_type: TypedArrayType,
+        length: usize,
+        bytes: impl Iterator<Item = u8>,
+    ) -> Result<JSObject, JSException> {
+        let array = Self::new_typed_array(context, typed_array_type, length)?;
+        // An empty typed array may have no backing store at all.
+        if length == 0 {
+            return Ok(array);
+        }
+        // The buffer is only used before any other call into JavaScriptCore.
+        let buffer = unsafe { array.get_typed_array_buffer(context)? };
+        for (target, byte) in buffer.iter_mut().zip(bytes) {
+            *target = byte;
+        }
+        Ok(array)
+    }
+
+    /// Copies the elements of a `BigInt64Array`.
+    pub fn bigint64_array_values(&self, context: &JSContext) -> Result<Vec<i64>, JSException> {
+        self.typed_array_chunks(context, TypedArrayType::BigInt64Array, |chunk| {
+            i64::from_ne_bytes(chunk.try_into().unwrap())
+        })
+    }
+
+    /// Copies the elements of a `BigUint64Array`.
+    pub fn biguint64_array_values(&self, context: &JSContext) -> Result<Vec<u64>, JSException> {
+        self.typed_array_chunks(context, TypedArrayType::BigUint64Array, |chunk| {
+            u64::from_ne_bytes(chunk.try_into().unwrap())
+        })
+    }
+
+    fn typed_array_chunks<N>(
+        &self,
+        context: &JSContext,
+        typed_array_type: TypedArrayType,
+        from_bytes: impl Fn(&[u8]) -> N,
+    ) -> Result<Vec<N>, JSException> {
+        if self.typed_array_type(context) != Some(typed_array_type) {
+            return Err(conversion_error(
+                context,
+                format!("object is not a {}", typed_array_type.name()),
+            ));
+        }
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let byte_length =
+            unsafe { JSObjectGetTypedArrayByteLength(context.inner, self.inner, &mut exception) };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        // An empty typed array may have no backing store at all.
+        if byte_length == 0 {
+            return Ok(Vec::new());
+        }
+        // The buffer is copied before any other call into JavaScriptCore.
+        let buffer = unsafe { self.get_typed_array_buffer(context)? };
+        Ok(buffer
+            .chunks_exact(std::mem::size_of::<N>())
+            .map(from_bytes)
+            .collect())
+    }
+}
+
+macro_rules! bigint_conversions {
+    ($($ty:ty as $via:ty => $from:ident, $to:ident;)*) => {
+        $(
+            /// Converts to a number if the value is a safe integer, so that
+            /// scripts can compute with it like with any number, and to a
+            /// `bigint` otherwise.
+            impl IntoJSValue for $ty {
+                fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+                    let number = self as f64;
+                    if number.abs() <= MAX_SAFE_INTEGER {
+                        return Ok(JSValue::number(context, number));
+                    }
+                    JSValue::$from(context, self as $via)
+                }
+            }
+
+            /// Converts a `bigint` exactly, or a number if it's a safe
+            /// integer in the range of the type.
+            impl FromJSValue for $ty {
+                fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+                    if value.is_number(context) {
+                        let number = value.to_number(context)?;
+                        if number.fract() != 0.0
+                            || number.abs() > MAX_SAFE_INTEGER
+                            || number < <$ty>::MIN as f64
+                            || number > <$ty>::MAX as f64
+                        {
+                            return Err(conversion_error(
+                                context,
+                                format!("{number} is not a valid {}", stringify!($ty)),
+                            ));
+                        }
+                        return Ok(number as $ty);
+                    }
+                    let integer = value.$to(context)?;
+                    <$ty>::try_from(integer).map_err(|_| {
+                        conversion_error(
+                            context,
+                            format!("{integer} is out of the range of {}", stringify!($ty)),
+                        )
+                    })
+                }
+            }
+        )*
+    };
+}
+
+bigint_conversions! {
+    i64 as i64 => bigint_from_i64, bigint_to_i64;
+    u64 as u64 => bigint_from_u64, bigint_to_u64;
+    isize as i64 => bigint_from_i64, bigint_to_i64;
+    usize as u64 => bigint_from_u64, bigint_to_u64;
+    i128 as i128 => bigint_from_i128, bigint_to_i128;
+    u128 as u128 => bigint_from_u128, bigint_to_u128;
+}
@@ -0,0 +1,342 @@
+//! The built-in functions called by the crate, captured once per context.
+//!
+//! Scripts can replace the globals and the prototype methods of the built-in
+//! types, so the wrappers of the crate call the functions captured when the
+//! context is created rather than looking them up on each call. The captured
+//! methods also serve as brand checks: a built-in method like
+//! `Map.prototype.has` throws when called with an object of another type, so
+//! it recognizes the objects of any context, which a spoofed
+//! `Symbol.toStringTag` can't imitate.
+
+use rusty_jsc_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{JSContext, JSException, JSObject, JSValue};
+
+/// Declares the captured built-ins, with the way each one is looked up.
+macro_rules! builtins {
+    ($($(#[$doc:meta])* $name:ident = $capture:ident($($arg:literal),*);)*) => {
+        /// The built-ins of a context, protected from garbage collection
+        /// until the context is forgotten.
+        pub(crate) struct Builtins {
+            context: JSGlobalContextRef,
+            $($(#[$doc])* pub(crate) $name: JSValue,)*
+        }
+
+        impl Builtins {
+            fn capture(context: &JSContext) -> Result<Self, JSException> {
+                let global = context.get_global_object();
+                $(let $name = $capture(context, &global, $($arg),*)?;)*
+                $(unsafe { JSValueProtect(context.global_context, $name.inner) };)*
+                Ok(Self {
+                    context: context.global_context,
+                    $($name,)*
+                })
+            }
+        }
+
+        impl Drop for Builtins {
+            fn drop(&mut self) {
+                $(unsafe { JSValueUnprotect(self.context, self.$name.inner) };)*
+            }
+        }
+    };
+}
+
+builtins! {
+    object = global("Object");
+    object_keys = static_method("Object", "keys");
+    object_get_own_property_symbols = static_method("Object", "getOwnPropertySymbols");
+    object_get_own_property_descriptor = static_method("Object", "getOwnPropertyDescriptor");
+    object_property_is_enumerable = method("Object", "propertyIsEnumerable");
+    object_to_string = method("Object", "toString");
+    /// `Function.prototype`, which is callable and does nothing.
+    function_prototype = prototype("Function");
+    to_string_tag = symbol("toStringTag");
+
+    map = global("Map");
+    map_get = method("Map", "get");
+    map_set = method("Map", "set");
+    map_has = method("Map", "has");
+    map_delete = method("Map", "delete");
+    map_clear = method("Map", "clear");
+    map_entries = method("Map", "entries");
+    map_keys = method("Map", "keys");
+    map_values = method("Map", "values");
+    map_size = getter("Map", "size");
+    map_iterator_next = iterator_next("Map");
+
+    set = global("Set");
+    set_add = method("Set", "add");
+    set_has = method("Set", "has");
+    set_delete = method("Set", "delete");
+    set_clear = method("Set", "clear");
+    set_values = method("Set", "values");
+    set_size = getter("Set", "size");
+    set_iterator_next = iterator_next("Set");
+
+    weak_map = global("WeakMap");
+    weak_map_get = method("WeakMap", "get");
+    weak_map_set = method("WeakMap", "set");
+    weak_map_has = method("WeakMap", "has");
+    weak_map_delete = method("WeakMap", "delete");
+
+    weak_set_has = method("WeakSet", "has");
+
+    weak_ref = global("WeakRef");
+    weak_ref_deref = method("WeakRef", "deref");
+
+    promise_then = method("Promise", "then");
+
+    regexp_prototype = prototype("RegExp");
+    regexp_source = getter("RegExp", "source");
+    regexp_flags = getter("RegExp", "flags");
+    regexp_to_string = method("RegExp", "toString");
+}
+
+fn global(context: &JSContext, global: &JSObject, name: &str) -> Result<JSValue, JSException> {
+    global.get_property(context, name)
+}
+
+fn prototype(
+    context: &JSContext,
+    global: &JSObject,
+    constructor: &str,
+) -> Result<JSValue, JSException> {
+    global
+        .get_property(context, constructor)?
+        .to_object(context)?
+        .get_property(context, "prototype")
+}
+
+fn static_method(
+    context: &JSContext,
+    global: &JSObject,
+    constructor: &str,
+    name: &str,
+) -> Result<JSValue, JSException> {
+    global
+        .get_property(context, constructor)?
+        .to_object(context)?
+        .get_property(context, name)
+}
+
+fn method(
+    context: &JSContext,
+    global: &JSObject,
+    constructor: &str,
+    name: &str,
+) -> Result<JSValue, JSException> {
+    prototype(context, global, constructor)?
+        .to_object(context)?
+        .get_property(context, name)
+}
+
+/// Looks up the getter of an accessor of a prototype, e.g. `Map.prototype.size`.
+fn getter(
+    context: &JSContext,
+    global: &JSObject,
+    constructor: &str,
+    name: &str,
+) -> Result<JSValue, JSException> {
+    let prototype = prototype(context, global, constructor)?;
+    global
+        .get_property(context, "Object")?
+        .to_object(context)?
+        .get_property(context, "getOwnPropertyDescriptor")?
+        .to_object(context)?
+        .call_as_function(context, None, &[prototype, JSValue::string(context, name)])?
+        .to_object(context)?
+        .get_property(context, "get")
+}
+
+/// Looks up the shared `next` method of the iterators of a collection.
+fn iterator_next(
+    context: &JSContext,
+    global: &JSObject,
+    constructor: &str,
+) -> Result<JSValue, JSException> {
+    let collection = global
+        .get_property(context, constructor)?
+        .to_object(context)?
+        .construct(context, &[])?;
+    collection
+        .get_property(context, "values")?
+        .to_object(context)?
+        .call_as_function(context, Some(&collection), &[])?
+        .to_object(context)?
+        .get_prototype(context)
+        .to_object(context)?
+        .get_property(context, "next")
+}
+
+fn symbol(context: &JSContext, global: &JSObject, name: &str) -> Result<JSValue, JSException> {
+    global
+        .get_property(context, "Symbol")?
+        .to_object(context)?
+        .get_property(context, name)
+}
+
+/// The built-in types recognized by a brand check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Brand {
+    Map,
+    Set,
+    WeakMap,
+    WeakSet,
+    WeakRef,
+    Promise,
+    RegExp,
+    Error,
+}
+
+impl Brand {
+    fn from_tag(tag: &str) -> Option<Self> {
+        let brand = match tag {
+            "Map" => Brand::Map,
+            "Set" => Brand::Set,
+            "WeakMap" => Brand::WeakMap,
+            "WeakSet" => Brand::WeakSet,
+            "WeakRef" => Brand::WeakRef,
+            "Promise" => Brand::Promise,
+            "RegExp" => Brand::RegExp,
+            "Error" => Brand::Error,
+            _ => return None,
+        };
+        Some(brand)
+    }
+}
+
+impl Builtins {
+    /// Calls a captured function with `this`, which must be an object.
+    pub(crate) fn call(
+        &self,
+        context: &JSContext,
+        function: &JSValue,
+        this: &JSValue,
+        args: &[JSValue],
+    ) -> Result<JSValue, JSException> {
+        let function: JSObject = JSObject::from(function.inner as JSObjectRef);
+        let this: JSObject = JSObject::from(this.inner as JSObjectRef);
+        function.call_as_function(context, Some(&this), args)
+    }
+
+    /// Calls a captured constructor.
+    pub(crate) fn construct(
+        &self,
+        context: &JSContext,
+        constructor: &JSValue,
+        args: &[JSValue],
+    ) -> Result<JSObject, JSException> {
+        let constructor: JSObject = JSObject::from(constructor.inner as JSObjectRef);
+        constructor.construct(context, args)
+    }
+
+    /// Returns the `Object.prototype.toString` tag of an object, e.g.
+    /// `Map` for `[object Map]`.
+    ///
+    /// The tag can be spoofed with `Symbol.toStringTag`, so it's only a hint
+    /// to pick the brand check to run.
+    pub(crate) fn tag(&self, context: &JSContext, value: &JSValue) -> Option<String> {
+        let tag = self
+            .call(context, &self.object_to_string, value, &[])
+            .ok()?;
+        let tag = tag.to_js_string(context).ok()?.to_string();
+        tag.strip_prefix("[object ")
+            .and_then(|tag| tag.strip_suffix(']'))
+            .map(str::to_owned)
+    }
+
+    /// Checks if a value is a `brand` object, of any context.
+    ///
+    /// Checking a `Promise` calls `Promise.prototype.then`, which marks the
+    /// promise as handled, and checking a `WeakRef` keeps its target alive
+    /// until the end of the current job. An `Error` has no brand check, so it
+    /// is recognized by its tag, as long as the object has no
+    /// `Symbol.toStringTag` that could have produced the tag.
+    pub(crate) fn is(&self, context: &JSContext, value: &JSValue, brand: Brand) -> bool {
+        if !value.is_object(context) {
+            return false;
+        }
+        let succeeds = |function: &JSValue, args: &[JSValue]| {
+            self.call(context, function, value, args).is_ok()
+        };
+        match brand {
+            Brand::Map => succeeds(&self.map_has, &[]),
+            Brand::Set => succeeds(&self.set_has, &[]),
+            Brand::WeakMap => succeeds(&self.weak_map_has, &[]),
+            Brand::WeakSet => succeeds(&self.weak_set_has, &[]),
+            Brand::WeakRef => succeeds(&self.weak_ref_deref, &[]),
+            // The rejection handler keeps the derived promise from being
+            // reported as an unhandled rejection.
+            Brand::Promise => succeeds(
+                &self.promise_then,
+                &[JSValue::undefined(context), self.function_prototype.clone()],
+            ),
+            // The getter accepts `RegExp.prototype`, which isn't a `RegExp`.
+            Brand::RegExp => {
+                !unsafe {
+                    JSValueIsStrictEqual(context.inner, value.inner, self.regexp_prototype.inner)
+                } && succeeds(&self.regexp_source, &[])
+            }
+            Brand::Error => {
+                self.tag(context, value).as_deref() == Some("Error")
+                    && !unsafe {
+                        JSObjectHasPropertyForKey(
+                            context.inner,
+                            value.inner as JSObjectRef,
+                            self.to_string_tag.inner,
+                            std::ptr::null_mut(),
+                        )
+                    }
+            }
+        }
+    }
+
+    /// Returns the brand of a value, if it's one of the brand-checked
+    /// built-in objects.
+    ///
+    /// The tag picks the brand check to run, so that an ordinary object only
+    /// costs one call, and an object whose `Symbol.toStringTag` is changed
+    /// isn't recognized.
+    pub(crate) fn brand(&self, context: &JSContext, value: &JSValue) -> Option<Brand> {
+        if !value.is_object(context) {
+            return None;
+        }
+        let brand = Brand::from_tag(&self.tag(context, value)?)?;
+        self.is(context, value, brand).then_some(brand)
+    }
+}
+
+thread_local! {
+    /// The built-ins of each context, keyed by the global context.
+    static BUILTINS: RefCell<HashMap<usize, Rc<Builtins>>> = RefCell::new(HashMap::new());
+}
+
+/// Returns the built-ins of a context, capturing them on first use.
+///
+/// The contexts created by the crate capture them before running any
+/// script. The contexts wrapped from a raw `JSContextRef` capture them the
+/// first time they're needed.
+pub(crate) fn builtins(context: &JSContext) -> Result<Rc<Builtins>, JSException> {
+    let key = context.global_context as usize;
+    if let Some(builtins) = BUILTINS.with(|builtins| builtins.borrow().get(&key).cloned()) {
+        return Ok(builtins);
+    }
+    // The lookups can run scripts, so the table isn't borrowed meanwhile.
+    let builtins = Rc::new(Builtins::capture(context)?);
+    BUILTINS.with(|table| table.borrow_mut().insert(key, builtins.clone()));
+    Ok(builtins)
+}
+
+/// Releases the built-ins of the given context.
+pub(crate) fn forget_builtins(context: JSGlobalContextRef) {
+    // The table may already be destroyed if the context is dropped while the
+    // thread exits. The built-ins are dropped outside of the borrow.
+    let _builtins = BUILTINS
+        .try_with(|builtins| builtins.borrow_mut().remove(&(context as usize)))
+        .ok()
+        .flatten();
+}
@@ -0,0 +1,131 @@
+//! Typed helpers to call JavaScript functions from Rust.
+
+use crate::{
+    convert::conversion_error, FromJSValue, IntoJSArgs, IntoJSValue, JSContext, JSException,
+    JSObject, JSValue, PropertyKey,
+};
+use rusty_jsc_sys::*;
+
+impl<T> JSObject<T> {
+    /// Calls the object as a function, converting the arguments from Rust
+    /// values and the result into a Rust value.
+    ///
+    /// `this` is the value bound to `this` during the call. Pass `()` (i.e.
+    /// `undefined`) or `None` to use the global object, as for a plain
+    /// function call.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let add = context
+    ///     .evaluate_script("(a, b) => a + b", 1)
+    ///     .unwrap()
+    ///     .to_object(&context)
+    ///     .unwrap();
+    /// let sum = add.call::<f64>(&context, (), (1, 2)).unwrap();
+    /// assert_eq!(sum, 3.0);
+    /// ```
+    pub fn call<R: FromJSValue>(
+        &self,
+        context: &JSContext,
+        this: impl IntoJSValue,
+        args: impl IntoJSArgs,
+    ) -> Result<R, JSException> {
+        let this = this.into_js_value(context)?;
+        let args = args.into_js_args(context)?;
+        let result = self.call_with_this(context, &this, &args)?;
+        R::from_js_value(context, &result)
+    }
+
+    /// Calls the method `name` of the object, with the object as `this`.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let text = context
+    ///     .evaluate_script("new String('a,b,c')", 1)
+    ///     .unwrap()
+    ///     .to_object(&context)
+    ///     .unwrap();
+    /// let parts = text.call_method::<Vec<String>>(&context, "split", (",",)).unwrap();
+    /// assert_eq!(parts, ["a", "b", "c"]);
+    /// ```
+    pub fn call_method<R: FromJSValue>(
+        &self,
+        context: &JSContext,
+        name: impl Into<PropertyKey>,
+        args: impl IntoJSArgs,
+    ) -> Result<R, JSException> {
+        let method = self.get_property(context, name)?.to_object(context)?;
+        let this = JSValue::from(self.inner);
+        let args = args.into_js_args(context)?;
+        let result = method.call_with_this(context, &this, &args)?;
+        R::from_js_value(context, &result)
+    }
+
+    /// Creates a bound function, like `Function.prototype.bind`: calling it
+    /// calls this function with the given `this` and the given arguments
+    /// before the arguments of the call.
+    pub fn bind(
+        &self,
+        context: &JSContext,
+        this: impl IntoJSValue,
+        args: impl IntoJSArgs,
+    ) -> Result<JSObject, JSException> {
+        let mut bind_args = vec![this.into_js_value(context)?];
+        bind_args.extend(args.into_js_args(context)?);
+        self.call_method(context, "bind", bind_args)
+    }
+
+    /// Calls the object as a constructor, like the `new` operator, converting
+    /// the arguments from Rust values and the new instance into a Rust value.
+    pub fn new_instance<R: FromJSValue>(
+        &self,
+        context: &JSContext,
+        args: impl IntoJSArgs,
+    ) -> Result<R, JSException> {
+        let args = args.into_js_args(context)?;
+        if !unsafe { JSObjectIsConstructor(context.inner, self.inner) } {
+            return Err(conversion_error(context, "object is not a constructor"));
+        }
+        let instance = self.construct(context, &args)?;
+        R::from_js_value(context, &JSValue::from(instance.inner))
+    }
+
+    /// Calls the object as a function with any value as `this`.
+    fn call_with_this(
+        &self,
+        context: &JSContext,
+        this: &JSValue,
+        args: &[JSValue],
+    ) -> Result<JSValue, JSException> {
+        if !unsafe { JSObjectIsFunction(context.inner, self.inner) } {
+            return Err(conversion_error(context, "object is not a function"));
+        }
+        // Like in a sloppy mode call, `undefined` and `null` stand for the
+        // global object and primitives are wrapped into objects.
+        let this = if this.is_undefined(context) || this.is_null(context) {
+            std::ptr::null_mut()
+        } else {
+            this.to_object(context)?.inner
+        };
+        let args_refs = args.iter().map(|arg| arg.inner).collect::<Vec<_>>();
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let result = unsafe {
+            JSObjectCallAsFunction(
+                context.inner,
+                self.inner,
+                this,
+                args.len() as _,
+                args_refs.as_ptr(),
+                &mut exception,
+            )
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(JSValue::from(result))
+    }
+}
@@ -0,0 +1,513 @@
+//! Structured cloning of values between contexts and virtual machines.
+//!
+//! A value is first serialized into a `SerializedValue`, which holds only
+//! Rust data and can be sent to another thread, and then deserialized in the
+//! destination context. Like the HTML structured clone algorithm, the object
+//! graph is copied with its shared references and cycles, and functions,
+//! symbols and host objects can't be cloned.
+
+use rusty_jsc_sys::*;
+use std::collections::HashMap;
+
+use crate::builtins::{self, Brand};
+use crate::{
+    convert::conversion_error, JSContext, JSError, JSErrorKind, JSException, JSMap, JSObject,
+    JSObjectGeneric, JSSet, JSString, JSValue, JSValueIdentity, JSValueKind, TypedArrayType,
+};
+
+/// Clones values from one context into another, see `SerializedValue`.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, StructuredClone};
+///
+/// let mut source = JSContext::default();
+/// let destination = JSContext::default();
+/// let value = source
+///     .evaluate_script("({ list: [1, 2], when: new Date(0), tags: new Set(['a']) })", 1)
+///     .unwrap();
+/// let copy = StructuredClone::transfer(&source, &value, &destination).unwrap();
+/// assert!(copy.is_object(&destination));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StructuredClone {
+    transfer_array_buffers: bool,
+}
+
+impl StructuredClone {
+    /// Creates a cloner copying the array buffers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the array buffers are transferred rather than copied:
+    /// the source buffers are then detached, like the buffers given in the
+    /// transfer list of `postMessage`.
+    pub fn transfer_array_buffers(mut self, transfer: bool) -> Self {
+        self.transfer_array_buffers = transfer;
+        self
+    }
+
+    /// Copies `value` from the `source` context into the `destination`
+    /// context, with the default options.
+    pub fn transfer(
+        source: &JSContext,
+        value: &JSValue,
+        destination: &JSContext,
+    ) -> Result<JSValue, JSException> {
+        Self::new().clone_value(source, value, destination)
+    }
+
+    /// Copies `value` from the `source` context into the `destination`
+    /// context.
+    ///
+    /// An exception thrown while reading `value` belongs to `source`, and one
+    /// thrown while creating the copy belongs to `destination`: use
+    /// `serialize` and `SerializedValue::deserialize` to tell them apart.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, StructuredClone};
+    ///
+    /// let mut source = JSContext::default();
+    /// let destination = JSContext::default();
+    /// let weak_map = source.evaluate_script("new WeakMap()", 1).unwrap();
+    /// assert!(StructuredClone::new()
+    ///     .clone_value(&source, &weak_map, &destination)
+    ///     .is_err());
+    /// // Built-in objects are recognized by brand checks, not by their tag.
+    /// let spoofed = source
+    ///     .evaluate_script("({ [Symbol.toStringTag]: 'WeakMap' })", 1)
+    ///     .unwrap();
+    /// assert!(StructuredClone::new()
+    ///     .clone_value(&source, &spoofed, &destination)
+    ///     .is_ok());
+    /// ```
+    pub fn clone_value(
+        &self,
+        source: &JSContext,
+        value: &JSValue,
+        destination: &JSContext,
+    ) -> Result<JSValue, JSException> {
+        self.serialize(source, value)?.deserialize(destination)
+    }
+
+    /// Serializes `value`, which can then be deserialized in any context.
+    pub fn serialize(
+        &self,
+        context: &JSContext,
+        value: &JSValue,
+    ) -> Result<SerializedValue, JSException> {
+        let mut serializer = Serializer {
+            context,
+            options: *self,
+            objects: vec![],
+            indexes: HashMap::new(),
+            transferred: vec![],
+        };
+        let root = serializer.node(value)?;
+        // The buffers are detached once everything has been read from them,
+        // and only if all of them can be.
+        let transfers = serializer
+            .transferred
+            .iter()
+            .map(|buffer| transfer_method(context, buffer))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (buffer, transfer) in serializer.transferred.iter().zip(transfers) {
+            transfer.call_as_function(context, Some(buffer), &[JSValue::number(context, 0.0)])?;
+        }
+        Ok(SerializedValue {
+            root,
+            objects: serializer.objects.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+}
+
+/// A value serialized by `StructuredClone::serialize`.
+#[derive(Debug, Clone)]
+pub struct SerializedValue {
+    root: Node,
+    objects: Vec<ObjectNode>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(Vec<u16>),
+    BigInt(String),
+    /// The index of an object in `SerializedValue::objects`.
+    Object(usize),
+}
+
+#[derive(Debug, Clone)]
+enum ObjectNode {
+    Object(Vec<(String, Node)>),
+    Array {
+        length: u32,
+        properties: Vec<(String, Node)>,
+    },
+    Date(f64),
+    RegExp {
+        source: String,
+        flags: String,
+    },
+    Map(Vec<(Node, Node)>),
+    Set(Vec<Node>),
+    ArrayBuffer(Vec<u8>),
+    TypedArray {
+        typed_array_type: TypedArrayType,
+        /// The index of the array buffer.
+        buffer: usize,
+        byte_offset: usize,
+        length: usize,
+    },
+    Error {
+        name: String,
+        message: String,
+        stack: Option<String>,
+    },
+}
+
+struct Serializer<'a> {
+    context: &'a JSContext,
+    options: StructuredClone,
+    /// The serialized objects, `None` while their children are serialized.
+    objects: Vec<Option<ObjectNode>>,
+    /// The indexes of the objects already visited.
+    indexes: HashMap<JSValueIdentity, usize>,
+    transferred: Vec<JSObject>,
+}
+
+impl Serializer<'_> {
+    fn node(&mut self, value: &JSValue) -> Result<Node, JSException> {
+        let context = self.context;
+        let kind = value.kind(context);
+        let node = match kind {
+            JSValueKind::Undefined => Node::Undefined,
+            JSValueKind::Null => Node::Null,
+            JSValueKind::Boolean => Node::Boolean(value.to_bool(context)),
+            JSValueKind::Number => Node::Number(value.to_number(context)?),
+            JSValueKind::String => Node::String(value.to_js_string(context)?.as_utf16().to_vec()),
+            JSValueKind::BigInt => Node::BigInt(value.to_js_string(context)?.to_string_lossy()),
+            JSValueKind::Symbol => {
+                return Err(conversion_error(context, "a symbol could not be cloned"))
+            }
+            _ => {
+                let identity = JSValueIdentity::from(value.clone());
+                if let Some(index) = self.indexes.get(&identity) {
+                    return Ok(Node::Object(*index));
+                }
+                let index = self.objects.len();
+                self.objects.push(None);
+                self.indexes.insert(identity, index);
+                let object = self.object(&value.to_object(context)?, kind)?;
+                self.objects[index] = Some(object);
+                Node::Object(index)
+            }
+        };
+        Ok(node)
+    }
+
+    fn object(&mut self, object: &JSObject, kind: JSValueKind) -> Result<ObjectNode, JSException> {
+        let context = self.context;
+        let node = match kind {
+            JSValueKind::Array => {
+                let length = object.get_property(context, "length")?.to_number(context)? as u32;
+                ObjectNode::Array {
+                    length,
+                    properties: self.properties(object)?,
+                }
+            }
+            JSValueKind::Date => {
+                let time = object.call_method::<f64>(context, "getTime", ())?;
+                ObjectNode::Date(time)
+            }
+            JSValueKind::ArrayBuffer => {
+                let bytes = array_buffer_bytes(context, object)?;
+                if self.options.transfer_array_buffers {
+                    self.transferred.push(object.clone());
+                }
+                ObjectNode::ArrayBuffer(bytes)
+            }
+            JSValueKind::TypedArray(typed_array_type) => {
+                let mut exception: JSValueRef = std::ptr::null_mut();
+                let (buffer, byte_offset, length) = unsafe {
+                    (
+                        JSObjectGetTypedArrayBuffer(context.inner, object.inner, &mut exception),
+                        JSObjectGetTypedArrayByteOffset(
+                            context.inner,
+                            object.inner,
+                            &mut exception,
+                        ),
+                        JSObjectGetTypedArrayLength(context.inner, object.inner, &mut exception),
+                    )
+                };
+                if !exception.is_null() {
+                    return Err(JSException::from(exception));
+                }
+                let Node::Object(buffer) = self.node(&JSValue::from(buffer))? else {
+                    unreachable!("an array buffer is an object");
+                };
+                ObjectNode::TypedArray {
+                    typed_array_type,
+                    buffer,
+                    byte_offset: byte_offset as usize,
+                    length: length as usize,
+                }
+            }
+            JSValueKind::Error => {
+                let string = |name: &str| -> Result<Option<String>, JSException> {
+                    let value = object.get_property(context, name)?;
+                    if value.is_undefined(context) {
+                        return Ok(None);
+                    }
+                    Ok(Some(value.to_js_string(context)?.to_string_lossy()))
+                };
+                ObjectNode::Error {
+                    name: string("name")?.unwrap_or_else(|| "Error".to_string()),
+                    message: string("message")?.unwrap_or_default(),
+                    stack: string("stack")?,
+                }
+            }
+            JSValueKind::Object => self.plain_object(object)?,
+            JSValueKind::Function => {
+                return Err(conversion_error(context, "a function could not be cloned"))
+            }
+            _ => return Err(conversion_error(context, "an object could not be cloned")),
+        };
+        Ok(node)
+    }
+
+    /// Serializes an object of the `Object` kind, which can still be one of
+    /// the built-in objects recognized by a brand check.
+    fn plain_object(&mut self, object: &JSObject) -> Result<ObjectNode, JSException> {
+        let context = self.context;
+        let builtins = builtins::builtins(context)?;
+        let value = JSValue::from(object.inner);
+        let node = match builtins.brand(context, &value) {
+            Some(Brand::RegExp) => {
+                let string = |getter: &JSValue| -> Result<String, JSException> {
+                    let value = builtins.call(context, getter, &value, &[])?;
+                    Ok(value.to_js_string(context)?.to_string_lossy())
+                };
+                ObjectNode::RegExp {
+                    source: string(&builtins.regexp_source)?,
+                    flags: string(&builtins.regexp_flags)?,
+                }
+            }
+            Some(Brand::Map) => {
+                let map = JSMap::from_object(context, object.clone())?;
+                let mut entries = vec![];
+                for entry in map.entries(context)? {
+                    let (key, value) = entry?;
+                    entries.push((self.node(&key)?, self.node(&value)?));
+                }
+                ObjectNode::Map(entries)
+            }
+            Some(Brand::Set) => {
+                let set = JSSet::from_object(context, object.clone())?;
+                let mut values = vec![];
+                for value in set.values(context)? {
+                    values.push(self.node(&value?)?);
+                }
+                ObjectNode::Set(values)
+            }
+            // The objects of a class are host objects, which only the host knows
+            // how to copy.
+            None if !crate::is_class_object(object.inner) => {
+                ObjectNode::Object(self.properties(object)?)
+            }
+            _ => return Err(conversion_error(context, "an object could not be cloned")),
+        };
+        Ok(node)
+    }
+
+    fn properties(&mut self, object: &JSObject) -> Result<Vec<(String, Node)>, JSException> {
+        let mut properties = vec![];
+        for entry in object.entries(self.context) {
+            let (name, value) = entry?;
+            properties.push((name, self.node(&value)?));
+        }
+        Ok(properties)
+    }
+}
+
+fn array_buffer_bytes(context: &JSContext, buffer: &JSObject) -> Result<Vec<u8>, JSException> {
+    let mut exception: JSValueRef = std::ptr::null_mut();
+    let length =
+        unsafe { JSObjectGetArrayBufferByteLength(context.inner, buffer.inner, &mut exception) };
+    if !exception.is_null() {
+        return Err(JSException::from(exception));
+    }
+    if length == 0 {
+        return Ok(vec![]);
+    }
+    let bytes =
+        unsafe { JSObjectGetArrayBufferBytesPtr(context.inner, buffer.inner, &mut exception) };
+    if !exception.is_null() {
+        return Err(JSException::from(exception));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(bytes as *const u8, length as usize) }.to_vec())
+}
+
+/// Returns the `ArrayBuffer.prototype.transfer` method of a buffer, which
+/// detaches it.
+fn transfer_method(context: &JSContext, buffer: &JSObject) -> Result<JSObject, JSException> {
+    let transfer = buffer.get_property(context, "transfer")?;
+    if !transfer.is_object(context) {
+        return Err(conversion_error(
+            context,
+            "array buffers can't be detached by this version of JavaScriptCore",
+        ));
+    }
+    transfer.to_object(context)
+}
+
+impl SerializedValue {
+    /// Creates the value in `context`.
+    pub fn deserialize(&self, context: &JSContext) -> Result<JSValue, JSException> {
+        // The objects are created first, so that the references between them,
+        // including the cycles, can be set afterwards.
+        let mut objects: Vec<Option<JSObject>> = vec![None; self.objects.len()];
+        for (index, object) in self.objects.iter().enumerate() {
+            if !matches!(object, ObjectNode::TypedArray { .. }) {
+                objects[index] = Some(create_object(context, object)?);
+            }
+        }
+        for (index, object) in self.objects.iter().enumerate() {
+            if let ObjectNode::TypedArray {
+                typed_array_type,
+                buffer,
+                byte_offset,
+                length,
+            } = object
+            {
+                let buffer = objects[*buffer].as_ref().unwrap();
+                let mut exception: JSValueRef = std::ptr::null_mut();
+                let array = unsafe {
+                    JSObjectMakeTypedArrayWithArrayBufferAndOffset(
+                        context.inner,
+                        typed_array_type.to_raw(),
+                        buffer.inner,
+                        *byte_offset as _,
+                        *length as _,
+                        &mut exception,
+                    )
+                };
+                if !exception.is_null() {
+                    return Err(JSException::from(exception));
+                }
+                objects[index] = Some(JSObject::from(array));
+            }
+        }
+        let objects = objects.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+        for (object, node) in objects.iter().zip(&self.objects) {
+            match node {
+                ObjectNode::Object(properties) | ObjectNode::Array { properties, .. } => {
+                    let mut object = object.clone();
+                    for (name, value) in properties {
+                        let value = value.to_value(context, &objects)?;
+                        object.set_property(context, name.as_str(), value)?;
+                    }
+                }
+                ObjectNode::Map(entries) => {
+                    let map = JSMap::from_object(context, object.clone())?;
+                    for (key, value) in entries {
+                        map.set(
+                            context,
+                            key.to_value(context, &objects)?,
+                            value.to_value(context, &objects)?,
+                        )?;
+                    }
+                }
+                ObjectNode::Set(values) => {
+                    let set = JSSet::from_object(context, object.clone())?;
+                    for value in values {
+                        set.add(context, value.to_value(context, &objects)?)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.root.to_value(context, &objects)
+    }
+}
+
+impl Node {
+    fn to_value(&self, context: &JSContext, objects: &[JSObject]) -> Result<JSValue, JSException> {
+        let value = match self {
+            Node::Undefined => JSValue::undefined(context),
+            Node::Null => JSValue::null(context),
+            Node::Boolean(value) => JSValue::boolean(context, *value),
+            Node::Number(value) => JSValue::number(context, *value),
+            Node::String(value) => JSValue::string(context, JSString::from_utf16(value)),
+            Node::BigInt(value) => JSValue::bigint_from_str(context, value)?,
+            Node::Object(index) => JSValue::from(objects[*index].inner),
+        };
+        Ok(value)
+    }
+}
+
+/// Creates the object of a node, without its properties or entries.
+fn create_object(context: &JSContext, node: &ObjectNode) -> Result<JSObject, JSException> {
+    let global = context.get_global_object();
+    let construct = |name: &str, args: &[JSValue]| -> Result<JSObject, JSException> {
+        let constructor = global.get_property(context, name)?.to_object(context)?;
+        constructor.construct(context, args)
+    };
+    let object = match node {
+        ObjectNode::Object(_) => JSObject::<JSObjectGeneric>::new(context),
+        ObjectNode::Array { length, .. } => {
+            construct("Array", &[JSValue::number(context, *length as f64)])?
+        }
+        ObjectNode::Date(time) => construct("Date", &[JSValue::number(context, *time)])?,
+        ObjectNode::RegExp { source, flags } => construct(
+            "RegExp",
+            &[
+                JSValue::string(context, source.as_str()),
+                JSValue::string(context, flags.as_str()),
+            ],
+        )?,
+        ObjectNode::Map(_) => JSMap::new(context)?.into_object(),
+        ObjectNode::Set(_) => JSSet::new(context)?.into_object(),
+        ObjectNode::ArrayBuffer(bytes) => {
+            let buffer = construct(
+                "ArrayBuffer",
+                &[JSValue::number(context, bytes.len() as f64)],
+            )?;
+            if !bytes.is_empty() {
+                let mut exception: JSValueRef = std::ptr::null_mut();
+                let target = unsafe {
+                    JSObjectGetArrayBufferBytesPtr(context.inner, buffer.inner, &mut exception)
+                };
+                if !exception.is_null() {
+                    return Err(JSException::from(exception));
+                }
+                unsafe {
+                    std::ptr::copy_nonoverlapping(bytes.as_ptr(), target as *mut u8, bytes.len())
+                };
+            }
+            buffer
+        }
+        ObjectNode::Error {
+            name,
+            message,
+            stack,
+        } => {
+            // Only the native error types are recreated, other errors are
+            // created as plain `Error`s.
+            let kind = JSErrorKind::from_name(name).unwrap_or_default();
+            let mut error = JSError::with_kind(kind, message.as_str())
+                .to_js_value(context)
+                .to_object(context)?;
+            if let Some(stack) = stack {
+                error.set_property(context, "stack", JSValue::string(context, stack.as_str()))?;
+            }
+            error
+        }
+        ObjectNode::TypedArray { .. } => unreachable!("typed arrays are created with their buffer"),
+    };
+    Ok(object)
+}
@@ -0,0 +1,605 @@
+//! Typed wrappers around the keyed collections of JavaScript: `Map`, `Set`,
+//! `WeakMap` and `WeakRef`.
+//!
+//! The wrappers call the built-in methods captured when the context was
+//! created, so scripts replacing them, e.g. `Map.prototype.get`, don't change
+//! what the wrappers do.
+
+use crate::builtins::{self, Brand, Builtins};
+use crate::{
+    convert::conversion_error, FromJSValue, IntoJSValue, JSContext, JSException, JSIterator,
+    JSObject, JSValue,
+};
+use rusty_jsc_sys::JSObjectRef;
+use std::collections::{HashMap, HashSet};
+
+/// Creates a new instance of a captured built-in constructor.
+fn construct_builtin(
+    context: &JSContext,
+    constructor: fn(&Builtins) -> &JSValue,
+    args: &[JSValue],
+) -> Result<JSObject, JSException> {
+    let builtins = builtins::builtins(context)?;
+    builtins.construct(context, constructor(&builtins), args)
+}
+
+/// Wraps an object after checking it's a `brand`, with a brand check.
+fn downcast_object<T>(
+    context: &JSContext,
+    object: JSObject<T>,
+    brand: Brand,
+) -> Result<JSObject, JSException> {
+    let value = JSValue::from(object.inner);
+    if !builtins::builtins(context)?.is(context, &value, brand) {
+        return Err(conversion_error(
+            context,
+            format!("object is not a {brand:?}"),
+        ));
+    }
+    Ok(JSObject::from(object.inner))
+}
+
+/// Calls a captured built-in method with an object as `this`.
+fn call_builtin(
+    context: &JSContext,
+    object: &JSObject,
+    method: fn(&Builtins) -> &JSValue,
+    args: &[JSValue],
+) -> Result<JSValue, JSException> {
+    let builtins = builtins::builtins(context)?;
+    builtins.call(
+        context,
+        method(&builtins),
+        &JSValue::from(object.inner),
+        args,
+    )
+}
+
+/// Iterates with a captured built-in method returning an iterator, and the
+/// captured `next` method of its iterators.
+fn iterate_builtin<'a>(
+    context: &'a JSContext,
+    object: &JSObject,
+    method: fn(&Builtins) -> &JSValue,
+    next: fn(&Builtins) -> &JSValue,
+) -> Result<JSIterator<'a>, JSException> {
+    let builtins = builtins::builtins(context)?;
+    let iterator = builtins
+        .call(
+            context,
+            method(&builtins),
+            &JSValue::from(object.inner),
+            &[],
+        )?
+        .to_object(context)?;
+    let next = JSObject::from(next(&builtins).inner as JSObjectRef);
+    Ok(JSIterator::new(context, iterator, next))
+}
+
+/// Splits a `[key, value]` entry returned by a `Map` iterator.
+fn split_entry(context: &JSContext, entry: JSValue) -> Result<(JSValue, JSValue), JSException> {
+    let entry = entry.to_object(context)?;
+    Ok((
+        entry.get_property_at_index(context, 0)?,
+        entry.get_property_at_index(context, 1)?,
+    ))
+}
+
+/// A JavaScript `Map`.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, JSMap};
+///
+/// let context = JSContext::default();
+/// let map = JSMap::new(&context).unwrap();
+/// map.set(&context, "answer", 42).unwrap();
+/// assert_eq!(map.size(&context).unwrap(), 1);
+/// let answer = map.get(&context, "answer").unwrap();
+/// assert_eq!(answer.to_number(&context).unwrap(), 42.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct JSMap {
+    object: JSObject,
+}
+
+impl JSMap {
+    /// Creates an empty `Map`.
+    pub fn new(context: &JSContext) -> Result<Self, JSException> {
+        let object = construct_builtin(context, |builtins| &builtins.map, &[])?;
+        Ok(Self { object })
+    }
+
+    /// Creates a `Map` from `(key, value)` pairs.
+    pub fn from_entries<K, V>(
+        context: &JSContext,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, JSException>
+    where
+        K: IntoJSValue,
+        V: IntoJSValue,
+    {
+        let map = Self::new(context)?;
+        for (key, value) in entries {
+            map.set(context, key, value)?;
+        }
+        Ok(map)
+    }
+
+    /// Wraps an object, failing if it isn't a `Map`.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, JSMap};
+    ///
+    /// let mut context = JSContext::default();
+    /// let spoofed = context
+    ///     .evaluate_script("({ [Symbol.toStringTag]: 'Map' })", 1)
+    ///     .unwrap()
+    ///     .to_object(&context)
+    ///     .unwrap();
+    /// assert!(JSMap::from_object(&context, spoofed).is_err());
+    ///
+    /// // The wrapper calls the original methods.
+    /// context
+    ///     .evaluate_script("Map.prototype.get = () => 'patched'", 1)
+    ///     .unwrap();
+    /// let map = JSMap::new(&context).unwrap();
+    /// map.set(&context, "answer", 42).unwrap();
+    /// let answer = map.get(&context, "answer").unwrap();
+    /// assert_eq!(answer.to_number(&context).unwrap(), 42.0);
+    /// ```
+    pub fn from_object<T>(context: &JSContext, object: JSObject<T>) -> Result<Self, JSException> {
+        let object = downcast_object(context, object, Brand::Map)?;
+        Ok(Self { object })
+    }
+
+    /// Returns the underlying object.
+    pub fn as_object(&self) -> &JSObject {
+        &self.object
+    }
+
+    /// Consumes the wrapper and returns the underlying object.
+    pub fn into_object(self) -> JSObject {
+        self.object
+    }
+
+    /// Gets the value associated with a key, or `undefined`.
+    pub fn get(&self, context: &JSContext, key: impl IntoJSValue) -> Result<JSValue, JSException> {
+        let key = key.into_js_value(context)?;
+        call_builtin(context, &self.object, |builtins| &builtins.map_get, &[key])
+    }
+
+    /// Associates a value with a key.
+    pub fn set(
+        &self,
+        context: &JSContext,
+        key: impl IntoJSValue,
+        value: impl IntoJSValue,
+    ) -> Result<(), JSException> {
+        let key = key.into_js_value(context)?;
+        let value = value.into_js_value(context)?;
+        call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.map_set,
+            &[key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Checks if a key is in the map.
+    pub fn has(&self, context: &JSContext, key: impl IntoJSValue) -> Result<bool, JSException> {
+        let key = key.into_js_value(context)?;
+        Ok(
+            call_builtin(context, &self.object, |builtins| &builtins.map_has, &[key])?
+                .to_bool(context),
+        )
+    }
+
+    /// Removes a key from the map, returning whether it was present.
+    pub fn delete(&self, context: &JSContext, key: impl IntoJSValue) -> Result<bool, JSException> {
+        let key = key.into_js_value(context)?;
+        Ok(call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.map_delete,
+            &[key],
+        )?
+        .to_bool(context))
+    }
+
+    /// Removes all the entries of the map.
+    pub fn clear(&self, context: &JSContext) -> Result<(), JSException> {
+        call_builtin(context, &self.object, |builtins| &builtins.map_clear, &[])?;
+        Ok(())
+    }
+
+    /// Returns the number of entries of the map.
+    pub fn size(&self, context: &JSContext) -> Result<usize, JSException> {
+        let size = call_builtin(context, &self.object, |builtins| &builtins.map_size, &[])?;
+        Ok(size.to_number(context)? as usize)
+    }
+
+    /// Iterates over the `(key, value)` entries of the map, in insertion
+    /// order.
+    pub fn entries<'a>(
+        &self,
+        context: &'a JSContext,
+    ) -> Result<impl Iterator<Item = Result<(JSValue, JSValue), JSException>> + 'a, JSException>
+    {
+        Ok(iterate_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.map_entries,
+            |builtins| &builtins.map_iterator_next,
+        )?
+        .map(move |entry| split_entry(context, entry?)))
+    }
+
+    /// Iterates over the keys of the map, in insertion order.
+    pub fn keys<'a>(&self, context: &'a JSContext) -> Result<JSIterator<'a>, JSException> {
+        iterate_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.map_keys,
+            |builtins| &builtins.map_iterator_next,
+        )
+    }
+
+    /// Iterates over the values of the map, in insertion order.
+    pub fn values<'a>(&self, context: &'a JSContext) -> Result<JSIterator<'a>, JSException> {
+        iterate_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.map_values,
+            |builtins| &builtins.map_iterator_next,
+        )
+    }
+
+    /// Collects the entries of the map into a `HashMap`.
+    pub fn to_hash_map<K, V>(&self, context: &JSContext) -> Result<HashMap<K, V>, JSException>
+    where
+        K: FromJSValue + Eq + std::hash::Hash,
+        V: FromJSValue,
+    {
+        self.entries(context)?
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((
+                    K::from_js_value(context, &key)?,
+                    V::from_js_value(context, &value)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// A JavaScript `Set`.
+#[derive(Debug, Clone)]
+pub struct JSSet {
+    object: JSObject,
+}
+
+impl JSSet {
+    /// Creates an empty `Set`.
+    pub fn new(context: &JSContext) -> Result<Self, JSException> {
+        let object = construct_builtin(context, |builtins| &builtins.set, &[])?;
+        Ok(Self { object })
+    }
+
+    /// Creates a `Set` from values.
+    pub fn from_values<T: IntoJSValue>(
+        context: &JSContext,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<Self, JSException> {
+        let set = Self::new(context)?;
+        for value in values {
+            set.add(context, value)?;
+        }
+        Ok(set)
+    }
+
+    /// Wraps an object, failing if it isn't a `Set`.
+    pub fn from_object<T>(context: &JSContext, object: JSObject<T>) -> Result<Self, JSException> {
+        let object = downcast_object(context, object, Brand::Set)?;
+        Ok(Self { object })
+    }
+
+    /// Returns the underlying object.
+    pub fn as_object(&self) -> &JSObject {
+        &self.object
+    }
+
+    /// Consumes the wrapper and returns the underlying object.
+    pub fn into_object(self) -> JSObject {
+        self.object
+    }
+
+    /// Adds a value to the set.
+    pub fn add(&self, context: &JSContext, value: impl IntoJSValue) -> Result<(), JSException> {
+        let value = value.into_js_value(context)?;
+        call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.set_add,
+            &[value],
+        )?;
+        Ok(())
+    }
+
+    /// Checks if a value is in the set.
+    pub fn has(&self, context: &JSContext, value: impl IntoJSValue) -> Result<bool, JSException> {
+        let value = value.into_js_value(context)?;
+        Ok(call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.set_has,
+            &[value],
+        )?
+        .to_bool(context))
+    }
+
+    /// Removes a value from the set, returning whether it was present.
+    pub fn delete(
+        &self,
+        context: &JSContext,
+        value: impl IntoJSValue,
+    ) -> Result<bool, JSException> {
+        let value = value.into_js_value(context)?;
+        Ok(call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.set_delete,
+            &[value],
+        )?
+        .to_bool(context))
+    }
+
+    /// Removes all the values of the set.
+    pub fn clear(&self, context: &JSContext) -> Result<(), JSException> {
+        call_builtin(context, &self.object, |builtins| &builtins.set_clear, &[])?;
+        Ok(())
+    }
+
+    /// Returns the number of values of the set.
+    pub fn size(&self, context: &JSContext) -> Result<usize, JSException> {
+        let size = call_builtin(context, &self.object, |builtins| &builtins.set_size, &[])?;
+        Ok(size.to_number(context)? as usize)
+    }
+
+    /// Iterates over the values of the set, in insertion order.
+    pub fn values<'a>(&self, context: &'a JSContext) -> Result<JSIterator<'a>, JSException> {
+        iterate_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.set_values,
+            |builtins| &builtins.set_iterator_next,
+        )
+    }
+
+    /// Collects the values of the set into a `HashSet`.
+    pub fn to_hash_set<T>(&self, context: &JSContext) -> Result<HashSet<T>, JSException>
+    where
+        T: FromJSValue + Eq + std::hash::Hash,
+    {
+        self.values(context)?
+            .map(|value| T::from_js_value(context, &value?))
+            .collect()
+    }
+}
+
+/// A JavaScript `WeakMap`, whose keys are objects held weakly.
+#[derive(Debug, Clone)]
+pub struct JSWeakMap {
+    object: JSObject,
+}
+
+impl JSWeakMap {
+    /// Creates an empty `WeakMap`.
+    pub fn new(context: &JSContext) -> Result<Self, JSException> {
+        let object = construct_builtin(context, |builtins| &builtins.weak_map, &[])?;
+        Ok(Self { object })
+    }
+
+    /// Wraps an object, failing if it isn't a `WeakMap`.
+    pub fn from_object<T>(context: &JSContext, object: JSObject<T>) -> Result<Self, JSException> {
+        let object = downcast_object(context, object, Brand::WeakMap)?;
+        Ok(Self { object })
+    }
+
+    /// Returns the underlying object.
+    pub fn as_object(&self) -> &JSObject {
+        &self.object
+    }
+
+    /// Consumes the wrapper and returns the underlying object.
+    pub fn into_object(self) -> JSObject {
+        self.object
+    }
+
+    /// Gets the value associated with an object, or `undefined`.
+    pub fn get<T>(&self, context: &JSContext, key: &JSObject<T>) -> Result<JSValue, JSException> {
+        call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.weak_map_get,
+            &[JSValue::from(key.inner)],
+        )
+    }
+
+    /// Associates a value with an object.
+    pub fn set<T>(
+        &self,
+        context: &JSContext,
+        key: &JSObject<T>,
+        value: impl IntoJSValue,
+    ) -> Result<(), JSException> {
+        let value = value.into_js_value(context)?;
+        call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.weak_map_set,
+            &[JSValue::from(key.inner), value],
+        )?;
+        Ok(())
+    }
+
+    /// Checks if an object is in the map.
+    pub fn has<T>(&self, context: &JSContext, key: &JSObject<T>) -> Result<bool, JSException> {
+        let has = call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.weak_map_has,
+            &[JSValue::from(key.inner)],
+        )?;
+        Ok(has.to_bool(context))
+    }
+
+    /// Removes an object from the map, returning whether it was present.
+    pub fn delete<T>(&self, context: &JSContext, key: &JSObject<T>) -> Result<bool, JSException> {
+        let deleted = call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.weak_map_delete,
+            &[JSValue::from(key.inner)],
+        )?;
+        Ok(deleted.to_bool(context))
+    }
+}
+
+/// A JavaScript `WeakRef`, which holds an object without keeping it alive.
+#[derive(Debug, Clone)]
+pub struct JSWeakRef {
+    object: JSObject,
+}
+
+impl JSWeakRef {
+    /// Creates a `WeakRef` to an object.
+    pub fn new<T>(context: &JSContext, target: &JSObject<T>) -> Result<Self, JSException> {
+        let object = construct_builtin(
+            context,
+            |builtins| &builtins.weak_ref,
+            &[JSValue::from(target.inner)],
+        )?;
+        Ok(Self { object })
+    }
+
+    /// Wraps an object, failing if it isn't a `WeakRef`.
+    pub fn from_object<T>(context: &JSContext, object: JSObject<T>) -> Result<Self, JSException> {
+        let object = downcast_object(context, object, Brand::WeakRef)?;
+        Ok(Self { object })
+    }
+
+    /// Returns the underlying object.
+    pub fn as_object(&self) -> &JSObject {
+        &self.object
+    }
+
+    /// Consumes the wrapper and returns the underlying object.
+    pub fn into_object(self) -> JSObject {
+        self.object
+    }
+
+    /// Returns the target object, or `None` if it has been garbage collected.
+    pub fn deref(&self, context: &JSContext) -> Result<Option<JSObject>, JSException> {
+        let target = call_builtin(
+            context,
+            &self.object,
+            |builtins| &builtins.weak_ref_deref,
+            &[],
+        )?;
+        if target.is_undefined(context) {
+            return Ok(None);
+        }
+        Ok(Some(target.to_object(context)?))
+    }
+}
+
+impl IntoJSValue for JSMap {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.object.into())
+    }
+}
+
+impl IntoJSValue for JSSet {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.object.into())
+    }
+}
+
+impl IntoJSValue for JSWeakMap {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.object.into())
+    }
+}
+
+impl IntoJSValue for JSWeakRef {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.object.into())
+    }
+}
+
+impl FromJSValue for JSMap {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Self::from_object(context, value.to_object(context)?)
+    }
+}
+
+impl FromJSValue for JSSet {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Self::from_object(context, value.to_object(context)?)
+    }
+}
+
+impl FromJSValue for JSWeakMap {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Self::from_object(context, value.to_object(context)?)
+    }
+}
+
+impl FromJSValue for JSWeakRef {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Self::from_object(context, value.to_object(context)?)
+    }
+}
+
+/// Converts to a `Map`.
+impl<K, V, S> IntoJSValue for HashMap<K, V, S>
+where
+    K: IntoJSValue,
+    V: IntoJSValue,
+{
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        JSMap::from_entries(context, self)?.into_js_value(context)
+    }
+}
+
+/// Converts to a `Set`.
+impl<T, S> IntoJSValue for HashSet<T, S>
+where
+    T: IntoJSValue,
+{
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        JSSet::from_values(context, self)?.into_js_value(context)
+    }
+}
+
+/// Converts a `Map`.
+impl<K, V> FromJSValue for HashMap<K, V>
+where
+    K: FromJSValue + Eq + std::hash::Hash,
+    V: FromJSValue,
+{
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        JSMap::from_js_value(context, value)?.to_hash_map(context)
+    }
+}
+
+/// Converts a `Set`.
+impl<T> FromJSValue for HashSet<T>
+where
+    T: FromJSValue + Eq + std::hash::Hash,
+{
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        JSSet::from_js_value(context, value)?.to_hash_set(context)
+    }
+}
@@ -0,0 +1,649 @@
+//! The `console` object, routing its output to a `ConsoleSink`.
+
+use rusty_jsc_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::inspect::{self, format_number, InspectOptions, Inspector};
+use crate::panic::settle_callback;
+use crate::{JSContext, JSException, JSObject, JSObjectGeneric, JSValue, JSValueKind};
+
+/// How deep `console.log` formats nested objects, like Node.js.
+const DEPTH: usize = 2;
+
+/// The severity of a console message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConsoleLevel {
+    /// `console.log`, `console.table`, `console.dir`, `console.count` and the
+    /// timers.
+    Log,
+    Info,
+    Debug,
+    /// `console.warn` and the warnings about unknown labels.
+    Warn,
+    /// `console.error` and the failed assertions.
+    Error,
+    /// `console.trace`, followed by the stack.
+    Trace,
+}
+
+/// Where the messages of `console` go, see `JSContext::install_console`.
+///
+/// The messages are already formatted and indented by `console.group`. Any
+/// `Fn(ConsoleLevel, &str)` closure is a sink.
+pub trait ConsoleSink {
+    fn write(&self, level: ConsoleLevel, message: &str);
+}
+
+impl<F: Fn(ConsoleLevel, &str)> ConsoleSink for F {
+    fn write(&self, level: ConsoleLevel, message: &str) {
+        self(level, message)
+    }
+}
+
+/// Writes warnings, errors and traces to the standard error and the other
+/// messages to the standard output, like Node.js.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdioSink;
+
+impl ConsoleSink for StdioSink {
+    fn write(&self, level: ConsoleLevel, message: &str) {
+        match level {
+            ConsoleLevel::Log | ConsoleLevel::Info | ConsoleLevel::Debug => println!("{message}"),
+            ConsoleLevel::Warn | ConsoleLevel::Error | ConsoleLevel::Trace => {
+                eprintln!("{message}")
+            }
+        }
+    }
+}
+
+/// Writes the messages to the `log` crate, with the `console` target.
+#[cfg(feature = "log")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+#[cfg(feature = "log")]
+impl ConsoleSink for LogSink {
+    fn write(&self, level: ConsoleLevel, message: &str) {
+        let level = match level {
+            ConsoleLevel::Log | ConsoleLevel::Info => log::Level::Info,
+            ConsoleLevel::Debug => log::Level::Debug,
+            ConsoleLevel::Warn => log::Level::Warn,
+            ConsoleLevel::Error => log::Level::Error,
+            ConsoleLevel::Trace => log::Level::Trace,
+        };
+        log::log!(target: "console", level, "{message}");
+    }
+}
+
+/// Writes the messages as `tracing` events, with the `console` target.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+#[cfg(feature = "tracing")]
+impl ConsoleSink for TracingSink {
+    fn write(&self, level: ConsoleLevel, message: &str) {
+        match level {
+            ConsoleLevel::Log | ConsoleLevel::Info => {
+                tracing::info!(target: "console", "{message}")
+            }
+            ConsoleLevel::Debug => tracing::debug!(target: "console", "{message}"),
+            ConsoleLevel::Warn => tracing::warn!(target: "console", "{message}"),
+            ConsoleLevel::Error => tracing::error!(target: "console", "{message}"),
+            ConsoleLevel::Trace => tracing::trace!(target: "console", "{message}"),
+        }
+    }
+}
+
+/// The state of the console of a global context.
+struct Console {
+    sink: Rc<dyn ConsoleSink>,
+    indentation: usize,
+    counts: HashMap<String, u64>,
+    timers: HashMap<String, Instant>,
+}
+
+thread_local! {
+    /// The consoles of the global contexts, keyed by their address.
+    ///
+    /// A context stays on the thread of its virtual machine, so its console
+    /// is always in the table of that thread. It's removed once the last
+    /// handle to the context is dropped, not with the handle that installed
+    /// it.
+    static CONSOLES: RefCell<HashMap<usize, Console>> = RefCell::new(HashMap::new());
+}
+
+type Method = fn(&JSContext, &[JSValue]) -> Result<(), JSException>;
+
+type NativeFunction = unsafe extern "C" fn(
+    JSContextRef,
+    JSObjectRef,
+    JSObjectRef,
+    size_t,
+    *const JSValueRef,
+    *mut JSValueRef,
+) -> JSValueRef;
+
+macro_rules! console_methods {
+    ($($name:literal => $method:ident,)*) => {
+        const METHODS: &[(&str, NativeFunction)] = &[$(($name, {
+            unsafe extern "C" fn trampoline(
+                ctx: JSContextRef,
+                _function: JSObjectRef,
+                _this: JSObjectRef,
+                argument_count: size_t,
+                arguments: *const JSValueRef,
+                exception: *mut JSValueRef,
+            ) -> JSValueRef {
+                call(ctx, argument_count, arguments, exception, $method)
+            }
+            trampoline
+        })),*];
+    };
+}
+
+console_methods! {
+    "log" => log,
+    "info" => info,
+    "debug" => debug,
+    "warn" => warn,
+    "error" => error,
+    "trace" => trace,
+    "dir" => dir,
+    "assert" => assert,
+    "count" => count,
+    "countReset" => count_reset,
+    "time" => time,
+    "timeLog" => time_log,
+    "timeEnd" => time_end,
+    "group" => group,
+    "groupCollapsed" => group,
+    "groupEnd" => group_end,
+    "table" => table,
+}
+
+/// Installs `console` on the global object of a context, see
+/// `JSContext::install_console`.
+pub(crate) fn install(
+    context: &JSContext,
+    sink: impl ConsoleSink + 'static,
+) -> Result<(), JSException> {
+    let mut console = JSObject::<JSObjectGeneric>::new(context);
+    for (name, method) in METHODS {
+        console.set_property(context, *name, JSValue::callback(context, Some(*method)))?;
+    }
+    context
+        .get_global_object()
+        .set_property(context, "console", console.into())?;
+    CONSOLES.with(|consoles| {
+        consoles.borrow_mut().insert(
+            context.global_context as usize,
+            Console {
+                sink: Rc::new(sink),
+                indentation: 0,
+                counts: HashMap::new(),
+                timers: HashMap::new(),
+            },
+        );
+    });
+    Ok(())
+}
+
+/// Releases the console of the given context.
+pub(crate) fn forget_console(context: JSGlobalContextRef) {
+    let _ = CONSOLES.try_with(|consoles| consoles.borrow_mut().remove(&(context as usize)));
+}
+
+unsafe fn call(
+    ctx: JSContextRef,
+    argument_count: size_t,
+    arguments: *const JSValueRef,
+    exception: *mut JSValueRef,
+    method: Method,
+) -> JSValueRef {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let context = JSContext::from(ctx);
+        let arguments: Vec<_> = (0..argument_count)
+            .map(|index| JSValue::from(*arguments.add(index as usize)))
+            .collect();
+        method(&context, &arguments)
+    }));
+    settle_callback(ctx, result, exception)
+}
+
+/// Runs `f` with the console of the context, if it has one.
+///
+/// The console must not be borrowed while formatting values, since getters
+/// may log themselves.
+fn with_console<R>(context: &JSContext, f: impl FnOnce(&mut Console) -> R) -> Option<R> {
+    CONSOLES.with(|consoles| {
+        consoles
+            .borrow_mut()
+            .get_mut(&(context.global_context as usize))
+            .map(f)
+    })
+}
+
+/// Writes a message to the sink, indented by the current group.
+fn write(context: &JSContext, level: ConsoleLevel, message: &str) {
+    let Some((sink, indentation)) = with_console(context, |console| {
+        (console.sink.clone(), console.indentation)
+    }) else {
+        return;
+    };
+    if indentation == 0 {
+        sink.write(level, message);
+    } else {
+        let indent = " ".repeat(indentation);
+        let message: Vec<_> = message
+            .lines()
+            .map(|line| format!("{indent}{line}"))
+            .collect();
+        sink.write(level, &message.join("\n"));
+    }
+}
+
+/// Formats the arguments of a console method like `util.format`, replacing
+/// the `%s`, `%d`, `%i`, `%f`, `%j`, `%o`, `%O` and `%c` specifiers of the
+/// first argument and appending the other arguments.
+fn format_arguments(context: &JSContext, arguments: &[JSValue]) -> Result<String, JSException> {
+    let Some((first, rest)) = arguments.split_first() else {
+        return Ok(String::new());
+    };
+    let mut rest = rest.iter();
+    let mut message = String::new();
+    if first.is_string(context) {
+        let template = first.to_js_string(context)?.to_string_lossy();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                message.push(c);
+                continue;
+            }
+            match chars.peek().copied() {
+                Some('%') => {
+                    chars.next();
+                    message.push('%');
+                }
+                Some(specifier @ ('s' | 'd' | 'i' | 'f' | 'j' | 'o' | 'O' | 'c')) => {
+                    chars.next();
+                    match rest.next() {
+                        Some(argument) => {
+                            message.push_str(&format_specifier(context, specifier, argument)?)
+                        }
+                        None => {
+                            message.push('%');
+                            message.push(specifier);
+                        }
+                    }
+                }
+                _ => message.push('%'),
+            }
+        }
+    } else {
+        message = inspect::display(context, first, options(DEPTH))?;
+    }
+    for argument in rest {
+        message.push(' ');
+        message.push_str(&inspect::display(context, argument, options(DEPTH))?);
+    }
+    Ok(message)
+}
+
+fn format_specifier(
+    context: &JSContext,
+    specifier: char,
+    argument: &JSValue,
+) -> Result<String, JSException> {
+    let number = || match argument.kind(context) {
+        JSValueKind::Symbol => f64::NAN,
+        _ => argument.to_number(context).unwrap_or(f64::NAN),
+    };
+    Ok(match specifier {
+        's' if argument.is_string(context) => argument.to_js_string(context)?.to_string_lossy(),
+        's' => Inspector::new(context, options(0)).inspect(argument)?,
+        'd' | 'i' if argument.kind(context) == JSValueKind::BigInt => {
+            Inspector::new(context, options(0)).inspect(argument)?
+        }
+        'd' => format_number(context, number())?,
+        'i' => format_number(context, number().trunc())?,
+        'f' => format_number(context, number())?,
+        'j' => {
+            let json = context
+                .get_global_object()
+                .get_property(context, "JSON")?
+                .to_object(context)?;
+            match json.call_method::<JSValue>(context, "stringify", (argument.clone(),)) {
+                Ok(json) if json.is_string(context) => {
+                    json.to_js_string(context)?.to_string_lossy()
+                }
+                Ok(_) => "undefined".to_string(),
+                Err(_) => "[Circular]".to_string(),
+            }
+        }
+        'o' => Inspector::new(context, options(4)).inspect(argument)?,
+        'O' => Inspector::new(context, options(DEPTH)).inspect(argument)?,
+        _ => String::new(),
+    })
+}
+
+/// Returns the options formatting values up to `depth` levels.
+fn options(depth: usize) -> InspectOptions {
+    InspectOptions {
+        depth: Some(depth),
+        ..Default::default()
+    }
+}
+
+/// Returns the label argument of the counters and timers.
+fn label(context: &JSContext, arguments: &[JSValue]) -> Result<String, JSException> {
+    match arguments.first() {
+        Some(label) if !label.is_undefined(context) => inspect::string_of(context, label),
+        _ => Ok("default".to_string()),
+    }
+}
+
+fn log(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    write(
+        context,
+        ConsoleLevel::Log,
+        &format_arguments(context, arguments)?,
+    );
+    Ok(())
+}
+
+fn info(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    write(
+        context,
+        ConsoleLevel::Info,
+        &format_arguments(context, arguments)?,
+    );
+    Ok(())
+}
+
+fn debug(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    write(
+        context,
+        ConsoleLevel::Debug,
+        &format_arguments(context, arguments)?,
+    );
+    Ok(())
+}
+
+fn warn(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    write(
+        context,
+        ConsoleLevel::Warn,
+        &format_arguments(context, arguments)?,
+    );
+    Ok(())
+}
+
+fn error(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    write(
+        context,
+        ConsoleLevel::Error,
+        &format_arguments(context, arguments)?,
+    );
+    Ok(())
+}
+
+fn trace(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let mut message = format!("Trace: {}", format_arguments(context, arguments)?);
+    let error = context
+        .get_global_object()
+        .get_property(context, "Error")?
+        .to_object(context)?
+        .construct(context, &[])?;
+    let stack = error.get_property(context, "stack")?;
+    if stack.is_string(context) {
+        // Skip the frame of `console.trace` itself.
+        for frame in stack
+            .to_js_string(context)?
+            .to_string_lossy()
+            .lines()
+            .skip(1)
+        {
+            message.push_str("\n    at ");
+            message.push_str(frame);
+        }
+    }
+    write(context, ConsoleLevel::Trace, &message);
+    Ok(())
+}
+
+fn dir(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let message = match arguments.first() {
+        Some(value) => Inspector::new(context, options(DEPTH)).inspect(value)?,
+        None => "undefined".to_string(),
+    };
+    write(context, ConsoleLevel::Log, &message);
+    Ok(())
+}
+
+fn assert(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    if arguments
+        .first()
+        .is_some_and(|value| value.to_bool(context))
+    {
+        return Ok(());
+    }
+    let message = match arguments.get(1..) {
+        Some(rest) if !rest.is_empty() => {
+            format!("Assertion failed: {}", format_arguments(context, rest)?)
+        }
+        _ => "Assertion failed".to_string(),
+    };
+    write(context, ConsoleLevel::Error, &message);
+    Ok(())
+}
+
+fn count(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let label = label(context, arguments)?;
+    let count = with_console(context, |console| {
+        let count = console.counts.entry(label.clone()).or_insert(0);
+        *count += 1;
+        *count
+    });
+    if let Some(count) = count {
+        write(context, ConsoleLevel::Log, &format!("{label}: {count}"));
+    }
+    Ok(())
+}
+
+fn count_reset(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let label = label(context, arguments)?;
+    let reset = with_console(context, |console| console.counts.remove(&label).is_some());
+    if reset == Some(false) {
+        let message = format!("Count for '{label}' does not exist");
+        write(context, ConsoleLevel::Warn, &message);
+    }
+    Ok(())
+}
+
+fn time(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let label = label(context, arguments)?;
+    let started = with_console(context, |console| {
+        if console.timers.contains_key(&label) {
+            return false;
+        }
+        console.timers.insert(label.clone(), Instant::now());
+        true
+    });
+    if started == Some(false) {
+        let message = format!("Label '{label}' already exists for console.time()");
+        write(context, ConsoleLevel::Warn, &message);
+    }
+    Ok(())
+}
+
+fn time_log(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    log_timer(context, arguments, "console.timeLog()", false)
+}
+
+fn time_end(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    log_timer(context, arguments, "console.timeEnd()", true)
+}
+
+fn log_timer(
+    context: &JSContext,
+    arguments: &[JSValue],
+    method: &str,
+    end: bool,
+) -> Result<(), JSException> {
+    let label = label(context, arguments)?;
+    let started = with_console(context, |console| {
+        if end {
+            console.timers.remove(&label)
+        } else {
+            console.timers.get(&label).copied()
+        }
+    });
+    let Some(started) = started else {
+        return Ok(());
+    };
+    let Some(started) = started else {
+        let message = format!("No such label '{label}' for {method}");
+        write(context, ConsoleLevel::Warn, &message);
+        return Ok(());
+    };
+    let elapsed = started.elapsed();
+    let elapsed = if elapsed.as_secs() >= 1 {
+        format!("{:.3}s", elapsed.as_secs_f64())
+    } else {
+        format!("{:.3}ms", elapsed.as_secs_f64() * 1000.0)
+    };
+    let mut message = format!("{label}: {elapsed}");
+    if !end && arguments.len() > 1 {
+        message.push(' ');
+        message.push_str(&format_arguments(context, &arguments[1..])?);
+    }
+    write(context, ConsoleLevel::Log, &message);
+    Ok(())
+}
+
+fn group(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    if !arguments.is_empty() {
+        write(
+            context,
+            ConsoleLevel::Log,
+            &format_arguments(context, arguments)?,
+        );
+    }
+    with_console(context, |console| console.indentation += 2);
+    Ok(())
+}
+
+fn group_end(context: &JSContext, _arguments: &[JSValue]) -> Result<(), JSException> {
+    with_console(context, |console| {
+        console.indentation = console.indentation.saturating_sub(2)
+    });
+    Ok(())
+}
+
+/// Prints the properties of an object, or the elements of an array, as the
+/// rows of a table, their own properties being the columns.
+fn table(context: &JSContext, arguments: &[JSValue]) -> Result<(), JSException> {
+    let Some(data) = arguments.first() else {
+        return log(context, arguments);
+    };
+    if !data.kind(context).is_object() || data.kind(context) == JSValueKind::Function {
+        return log(context, arguments);
+    }
+    let data = data.to_object(context)?;
+    let filter = match arguments.get(1) {
+        Some(columns) if columns.is_array(context) => Some(
+            columns
+                .to_object(context)?
+                .array_iter(context)?
+                .map(|column| inspect::string_of(context, &column?))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        _ => None,
+    };
+
+    let cell = |value: &JSValue| Inspector::new(context, options(0)).inspect(value);
+    let mut columns: Vec<String> = filter.clone().unwrap_or_default();
+    let mut rows = vec![];
+    let mut has_values = false;
+    for key in inspect::own_keys(context, &data)? {
+        let value = data.get_property(context, key.as_str())?;
+        let mut cells = HashMap::new();
+        let mut primitive = None;
+        let kind = value.kind(context);
+        if kind.is_object() && kind != JSValueKind::Function {
+            let row = value.to_object(context)?;
+            for column in inspect::own_keys(context, &row)? {
+                if filter.is_none() && !columns.contains(&column) {
+                    columns.push(column.clone());
+                }
+                if columns.contains(&column) {
+                    let value = row.get_property(context, column.as_str())?;
+                    cells.insert(column, cell(&value)?);
+                }
+            }
+        } else {
+            has_values = true;
+            primitive = Some(cell(&value)?);
+        }
+        rows.push((key, cells, primitive));
+    }
+
+    let mut header = vec!["(index)".to_string()];
+    header.extend(columns.iter().cloned());
+    if has_values {
+        header.push("Values".to_string());
+    }
+    let body: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|(key, mut cells, primitive)| {
+            let mut line = vec![key];
+            for column in &columns {
+                line.push(cells.remove(column).unwrap_or_default());
+            }
+            if has_values {
+                line.push(primitive.unwrap_or_default());
+            }
+            line
+        })
+        .collect();
+    write(context, ConsoleLevel::Log, &render_table(&header, &body));
+    Ok(())
+}
+
+/// Draws a table with box-drawing characters.
+fn render_table(header: &[String], body: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            std::iter::once(&header[column])
+                .chain(body.iter().map(|line| &line[column]))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+                + 2
+        })
+        .collect();
+    let border = |left: &str, middle: &str, right: &str| {
+        let lines: Vec<_> = widths.iter().map(|width| "─".repeat(*width)).collect();
+        format!("{left}{}{right}", lines.join(middle))
+    };
+    let line = |cells: &[String]| {
+        let cells: Vec<_> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let padding = width - 1 - cell.chars().count();
+                format!(" {cell}{}", " ".repeat(padding))
+            })
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+    let mut lines = vec![border("┌", "┬", "┐"), line(header), border("├", "┼", "┤")];
+    lines.extend(body.iter().map(|cells| line(cells)));
+    lines.push(border("└", "┴", "┘"));
+    lines.join("\n")
+}
@@ -0,0 +1,299 @@
+//! Conversions between Rust values and JavaScript values.
+//!
+//! `IntoJSValue` and `FromJSValue` are implemented for the primitive Rust
+//! types, strings, options, vectors and the wrapper types of this crate, so
+//! generic helpers can accept and return plain Rust values.
+//!
+//! ```rust
+//! use rusty_jsc::{FromJSValue, IntoJSValue, JSContext};
+//!
+//! let context = JSContext::default();
+//! let value = vec![1, 2, 3].into_js_value(&context).unwrap();
+//! let numbers = Vec::<u32>::from_js_value(&context, &value).unwrap();
+//! assert_eq!(numbers, [1, 2, 3]);
+//! ```
+
+use crate::{JSContext, JSError, JSException, JSObject, JSObjectGeneric, JSString, JSValue};
+
+/// A Rust value that can be converted into a `JSValue`.
+pub trait IntoJSValue {
+    /// Converts this value into a `JSValue` of the given context.
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException>;
+}
+
+/// A Rust value that can be extracted from a `JSValue`.
+pub trait FromJSValue: Sized {
+    /// Extracts a Rust value from a `JSValue` of the given context.
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException>;
+}
+
+/// Creates the exception returned when a value can't be converted, a
+/// `TypeError` with the given message.
+pub(crate) fn conversion_error(context: &JSContext, message: impl Into<String>) -> JSException {
+    JSError::type_error(message).to_js_value(context).into()
+}
+
+impl IntoJSValue for JSValue {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self)
+    }
+}
+
+impl IntoJSValue for &JSValue {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.clone())
+    }
+}
+
+/// Converts to the thrown value.
+impl IntoJSValue for JSException {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T> IntoJSValue for JSObject<T> {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::from(self.inner))
+    }
+}
+
+impl<T> IntoJSValue for &JSObject<T> {
+    fn into_js_value(self, _context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::from(self.inner))
+    }
+}
+
+impl IntoJSValue for JSString {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::string(context, self))
+    }
+}
+
+impl IntoJSValue for &str {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::string(context, self))
+    }
+}
+
+impl IntoJSValue for String {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::string(context, self))
+    }
+}
+
+impl IntoJSValue for &String {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::string(context, self.as_str()))
+    }
+}
+
+impl IntoJSValue for bool {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::boolean(context, self))
+    }
+}
+
+/// Converts to `undefined`.
+impl IntoJSValue for () {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(JSValue::undefined(context))
+    }
+}
+
+/// Converts `None` to `null`.
+impl<T: IntoJSValue> IntoJSValue for Option<T> {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        match self {
+            Some(value) => value.into_js_value(context),
+            None => Ok(JSValue::null(context)),
+        }
+    }
+}
+
+/// Converts to an `Array`.
+impl<T: IntoJSValue> IntoJSValue for Vec<T> {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        let elements = self
+            .into_iter()
+            .map(|element| element.into_js_value(context))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(JSObject::<JSObjectGeneric>::new_array(context, &elements)?.into())
+    }
+}
+
+macro_rules! number_into_js_value {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoJSValue for $ty {
+                fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+                    Ok(JSValue::number(context, self as f64))
+                }
+            }
+        )*
+    };
+}
+
+number_into_js_value!(f64, f32, i8, i16, i32, u8, u16, u32);
+
+impl FromJSValue for JSValue {
+    fn from_js_value(_context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJSValue for JSObject {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        value.to_object(context)
+    }
+}
+
+impl FromJSValue for JSString {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        value.to_js_string(context)
+    }
+}
+
+/// Fails if the string contains lone surrogates, see `JSString` for a lossy
+/// conversion.
+impl FromJSValue for String {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        value
+            .to_js_string(context)?
+            .try_to_string()
+            .map_err(|error| conversion_error(context, error.to_string()))
+    }
+}
+
+/// Converts with the JavaScript truthiness rules, i.e. `!!value`.
+impl FromJSValue for bool {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Ok(value.to_bool(context))
+    }
+}
+
+/// Accepts any value, as `undefined`.
+impl FromJSValue for () {
+    fn from_js_value(_context: &JSContext, _value: &JSValue) -> Result<Self, JSException> {
+        Ok(())
+    }
+}
+
+/// Converts `undefined` and `null` to `None`.
+impl<T: FromJSValue> FromJSValue for Option<T> {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        if value.is_undefined(context) || value.is_null(context) {
+            return Ok(None);
+        }
+        T::from_js_value(context, value).map(Some)
+    }
+}
+
+/// Converts an array-like object.
+impl<T: FromJSValue> FromJSValue for Vec<T> {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        value
+            .to_object(context)?
+            .array_iter(context)?
+            .map(|element| T::from_js_value(context, &element?))
+            .collect()
+    }
+}
+
+impl FromJSValue for f64 {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        value.to_number(context)
+    }
+}
+
+impl FromJSValue for f32 {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Ok(value.to_number(context)? as f32)
+    }
+}
+
+macro_rules! integer_from_js_value {
+    ($($ty:ty),*) => {
+        $(
+            /// Converts a number, failing if it isn't an integer in the range
+            /// of the type.
+            impl FromJSValue for $ty {
+                fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+                    let number = value.to_number(context)?;
+                    if number.fract() != 0.0
+                        || number < <$ty>::MIN as f64
+                        || number > <$ty>::MAX as f64
+                    {
+                        return Err(conversion_error(
+                            context,
+                            format!("{number} is not a valid {}", stringify!($ty)),
+                        ));
+                    }
+                    Ok(number as $ty)
+                }
+            }
+        )*
+    };
+}
+
+integer_from_js_value!(i8, i16, i32, u8, u16, u32);
+
+/// A list of Rust values that can be converted into the arguments of a
+/// JavaScript function call.
+///
+/// This is implemented for tuples of up to 8 `IntoJSValue` values, for
+/// arrays and vectors, and for slices of `JSValue`s. Use `()` to pass no
+/// arguments.
+pub trait IntoJSArgs {
+    /// Converts these values into a list of `JSValue`s of the given context.
+    fn into_js_args(self, context: &JSContext) -> Result<Vec<JSValue>, JSException>;
+}
+
+impl IntoJSArgs for () {
+    fn into_js_args(self, _context: &JSContext) -> Result<Vec<JSValue>, JSException> {
+        Ok(vec![])
+    }
+}
+
+impl IntoJSArgs for &[JSValue] {
+    fn into_js_args(self, _context: &JSContext) -> Result<Vec<JSValue>, JSException> {
+        Ok(self.to_vec())
+    }
+}
+
+impl<T: IntoJSValue> IntoJSArgs for Vec<T> {
+    fn into_js_args(self, context: &JSContext) -> Result<Vec<JSValue>, JSException> {
+        self.into_iter()
+            .map(|arg| arg.into_js_value(context))
+            .collect()
+    }
+}
+
+impl<T: IntoJSValue, const N: usize> IntoJSArgs for [T; N] {
+    fn into_js_args(self, context: &JSContext) -> Result<Vec<JSValue>, JSException> {
+        self.into_iter()
+            .map(|arg| arg.into_js_value(context))
+            .collect()
+    }
+}
+
+macro_rules! tuple_into_js_args {
+    ($($name:ident),+) => {
+        impl<$($name: IntoJSValue),+> IntoJSArgs for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_js_args(self, context: &JSContext) -> Result<Vec<JSValue>, JSException> {
+                let ($($name,)+) = self;
+                Ok(vec![$($name.into_js_value(context)?),+])
+            }
+        }
+    };
+}
+
+tuple_into_js_args!(A);
+tuple_into_js_args!(A, B);
+tuple_into_js_args!(A, B, C);
+tuple_into_js_args!(A, B, C, D);
+tuple_into_js_args!(A, B, C, D, E);
+tuple_into_js_args!(A, B, C, D, E, F);
+tuple_into_js_args!(A, B, C, D, E, F, G);
+tuple_into_js_args!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,163 @@
+//! Rust errors that are thrown as JavaScript errors.
+
+use std::fmt;
+
+use crate::{IntoJSValue, JSContext, JSException, JSObject, JSObjectGeneric, JSValue};
+
+/// The constructor of a `JSError`, one of the native error types of
+/// JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum JSErrorKind {
+    #[default]
+    Error,
+    TypeError,
+    RangeError,
+    SyntaxError,
+    ReferenceError,
+    EvalError,
+    URIError,
+    AggregateError,
+}
+
+impl JSErrorKind {
+    /// Returns the name of the constructor, e.g. `"TypeError"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            JSErrorKind::Error => "Error",
+            JSErrorKind::TypeError => "TypeError",
+            JSErrorKind::RangeError => "RangeError",
+            JSErrorKind::SyntaxError => "SyntaxError",
+            JSErrorKind::ReferenceError => "ReferenceError",
+            JSErrorKind::EvalError => "EvalError",
+            JSErrorKind::URIError => "URIError",
+            JSErrorKind::AggregateError => "AggregateError",
+        }
+    }
+
+    /// Returns the error type with the given constructor name, if it's one
+    /// of the native error types.
+    pub fn from_name(name: &str) -> Option<JSErrorKind> {
+        Some(match name {
+            "Error" => JSErrorKind::Error,
+            "TypeError" => JSErrorKind::TypeError,
+            "RangeError" => JSErrorKind::RangeError,
+            "SyntaxError" => JSErrorKind::SyntaxError,
+            "ReferenceError" => JSErrorKind::ReferenceError,
+            "EvalError" => JSErrorKind::EvalError,
+            "URIError" => JSErrorKind::URIError,
+            "AggregateError" => JSErrorKind::AggregateError,
+            _ => return None,
+        })
+    }
+}
+
+/// An error created on the Rust side, converted into a JavaScript error
+/// object when it's thrown.
+///
+/// Unlike a `JSException`, it doesn't hold any JavaScript value, so it can be
+/// sent across threads and kept across the awaits of an `async` callback.
+///
+/// ```rust
+/// use rusty_jsc::{IntoJSValue, JSContext, JSError};
+///
+/// let context = JSContext::default();
+/// let error = JSError::type_error("expected a path").into_js_value(&context).unwrap();
+/// let error = error.to_object(&context).unwrap();
+/// let name = error.get_property(&context, "name").unwrap();
+/// assert_eq!(name.to_js_string(&context).unwrap(), "TypeError");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JSError {
+    kind: JSErrorKind,
+    message: String,
+}
+
+impl JSError {
+    /// Creates an `Error` with the given message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::with_kind(JSErrorKind::Error, message)
+    }
+
+    /// Creates an error of the given type with the given message.
+    pub fn with_kind(kind: JSErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a `TypeError` with the given message.
+    pub fn type_error(message: impl Into<String>) -> Self {
+        Self::with_kind(JSErrorKind::TypeError, message)
+    }
+
+    /// Creates a `RangeError` with the given message.
+    pub fn range_error(message: impl Into<String>) -> Self {
+        Self::with_kind(JSErrorKind::RangeError, message)
+    }
+
+    /// Returns the type of the error.
+    pub fn kind(&self) -> JSErrorKind {
+        self.kind
+    }
+
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Creates the error object, with the constructor of the global object
+    /// if it's still a function, or as a plain `Error` otherwise.
+    pub(crate) fn to_js_value(&self, context: &JSContext) -> JSValue {
+        let construct = || -> Result<JSValue, JSException> {
+            let constructor = context
+                .get_global_object()
+                .get_property(context, self.kind.name())?
+                .to_object(context)?;
+            let message = JSValue::string(context, self.message.as_str());
+            let error = if self.kind == JSErrorKind::AggregateError {
+                let errors = JSObject::<JSObjectGeneric>::new_array(context, &[])?;
+                constructor.construct(context, &[errors.into(), message])?
+            } else {
+                constructor.construct(context, &[message])?
+            };
+            Ok(error.into())
+        };
+        construct().unwrap_or_else(|_| {
+            JSValue::from(unsafe { crate::panic::make_error(context.inner, &self.message) })
+        })
+    }
+}
+
+impl fmt::Display for JSError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.name(), self.message)
+    }
+}
+
+impl std::error::Error for JSError {}
+
+impl From<String> for JSError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<&str> for JSError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<std::io::Error> for JSError {
+    fn from(error: std::io::Error) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+/// Converts to a new error object.
+impl IntoJSValue for JSError {
+    fn into_js_value(self, context: &JSContext) -> Result<JSValue, JSException> {
+        Ok(self.to_js_value(context))
+    }
+}
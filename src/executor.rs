@@ -0,0 +1,181 @@
+//! A minimal executor running the futures of a context on its thread.
+//!
+//! JavaScriptCore values can't leave the thread of their context, so the
+//! futures spawned on a context, such as the ones of `async` callbacks, are
+//! polled by the thread owning the context, with `JSContext::run_pending_tasks`
+//! or `JSContext::run_tasks`. Their wakers can be used from any thread.
+
+use rusty_jsc_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use crate::{JSContext, JSException, JSObject, JSObjectGeneric, JSValue, Persistent};
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Arc<TaskWaker>,
+}
+
+struct TaskWaker {
+    woken: AtomicBool,
+    thread: Thread,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+thread_local! {
+    /// The pending tasks of the global contexts, keyed by their address.
+    static TASKS: RefCell<HashMap<usize, Vec<Task>>> = RefCell::new(HashMap::new());
+}
+
+fn global_context(context: &JSContext) -> usize {
+    unsafe { JSContextGetGlobalContext(context.inner) as usize }
+}
+
+pub(crate) fn spawn_local(context: &JSContext, future: impl Future<Output = ()> + 'static) {
+    let task = Task {
+        future: Box::pin(future),
+        waker: Arc::new(TaskWaker {
+            woken: AtomicBool::new(true),
+            thread: thread::current(),
+        }),
+    };
+    TASKS.with(|tasks| {
+        tasks
+            .borrow_mut()
+            .entry(global_context(context))
+            .or_default()
+            .push(task)
+    });
+}
+
+/// Polls the woken tasks of the context until none is woken, returning the
+/// number of tasks left.
+pub(crate) fn run_pending_tasks(context: &JSContext) -> usize {
+    let key = global_context(context);
+    loop {
+        // The tasks are taken out of the table while they are polled, so that
+        // they can spawn new tasks.
+        let mut pending = TASKS.with(|tasks| tasks.borrow_mut().remove(&key).unwrap_or_default());
+        let mut polled = false;
+        pending.retain_mut(|task| {
+            if !task.waker.woken.swap(false, Ordering::Acquire) {
+                return true;
+            }
+            polled = true;
+            let waker = Waker::from(task.waker.clone());
+            let mut cx = Context::from_waker(&waker);
+            task.future.as_mut().poll(&mut cx).is_pending()
+        });
+        let left = TASKS.with(|tasks| {
+            let mut tasks = tasks.borrow_mut();
+            let spawned = tasks.remove(&key).unwrap_or_default();
+            let polled_spawned = !spawned.is_empty();
+            pending.extend(spawned);
+            let left = pending.len();
+            if left > 0 {
+                tasks.insert(key, pending);
+            }
+            polled = polled || polled_spawned;
+            left
+        });
+        if !polled || left == 0 {
+            return left;
+        }
+    }
+}
+
+/// Runs the tasks of the context until they are all complete, parking the
+/// thread while none is woken.
+pub(crate) fn run_tasks(context: &JSContext) {
+    while run_pending_tasks(context) > 0 {
+        let woken = TASKS.with(|tasks| {
+            tasks
+                .borrow()
+                .get(&global_context(context))
+                .is_some_and(|pending| {
+                    pending
+                        .iter()
+                        .any(|task| task.waker.woken.load(Ordering::Acquire))
+                })
+        });
+        if !woken {
+            thread::park();
+        }
+    }
+}
+
+/// Drops the pending tasks of the given context.
+pub(crate) fn forget_tasks(context: JSGlobalContextRef) {
+    // The tasks are dropped outside of the table, since dropping them can
+    // touch it, and the table may already be destroyed if the context is
+    // dropped while the thread exits.
+    let tasks = TASKS
+        .try_with(|tasks| tasks.borrow_mut().remove(&(context as usize)))
+        .ok()
+        .flatten();
+    drop(tasks);
+}
+
+/// Spawns a future on the executor of the context and returns a promise
+/// settled with its result.
+///
+/// This is used by the functions generated by the `callback` macro for
+/// `async fn`s.
+#[doc(hidden)]
+pub fn spawn_promise(
+    context: &JSContext,
+    future: impl Future<Output = Result<JSValue, JSException>> + 'static,
+) -> JSValueRef {
+    let mut promise_context = JSContext::from(context.inner);
+    let promise = JSObject::<JSObjectGeneric>::promise(&mut promise_context);
+    let data = promise.data.as_ref().unwrap();
+    // The resolving functions are kept until the future completes, or until
+    // the task is dropped with the context.
+    let resolve = Persistent::new(context, &data.resolve);
+    let reject = Persistent::new(context, &data.reject);
+    let task_context = promise_context.inner;
+    let mut future = Box::pin(future);
+    spawn_local(context, async move {
+        let result = std::future::poll_fn(|cx| {
+            match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                Ok(poll) => poll,
+                Err(payload) => Poll::Ready(Err(JSException::from(
+                    crate::panic::handle_task_panic(task_context, payload),
+                ))),
+            }
+        })
+        .await;
+        let resolve = resolve.to_local(&promise_context);
+        let reject = reject.to_local(&promise_context);
+        // A promise whose resolution throws is rejected with the exception.
+        let settled = result
+            .and_then(|value| resolve.call_as_function(&promise_context, None, &[value]))
+            .or_else(|exception| {
+                reject.call_as_function(&promise_context, None, &[exception.into_value()])
+            });
+        if let Err(exception) = settled {
+            crate::panic::report(&format!(
+                "can't settle the promise of an async callback: {}",
+                exception.describe(&promise_context)
+            ));
+        }
+    });
+    JSValue::from(promise.inner).inner
+}
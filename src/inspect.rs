@@ -0,0 +1,989 @@
+//! Formatting of values for humans, in the style of `util.inspect` of
+//! Node.js.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::builtins::{self, Brand, Builtins};
+use crate::{
+    FromJSValue, JSContext, JSException, JSMap, JSObject, JSSet, JSValue, JSValueIdentity,
+    JSValueKind,
+};
+
+/// The width the output is kept in, when possible.
+const BREAK_LENGTH: usize = 80;
+
+/// How many levels of nested objects can be kept on one line, like the
+/// `compact` option of Node.js.
+const COMPACT: usize = 3;
+
+/// The options of `JSValue::inspect`.
+///
+/// ```rust
+/// use rusty_jsc::{InspectOptions, JSContext};
+///
+/// let mut context = JSContext::default();
+/// let nested = context.evaluate_script("({ a: { b: { c: { d: 1 } } } })", 1).unwrap();
+/// assert_eq!(
+///     nested.inspect(&context, InspectOptions::default()).unwrap(),
+///     "{ a: { b: { c: [Object] } } }",
+/// );
+/// let unlimited = InspectOptions {
+///     depth: None,
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     nested.inspect(&context, unlimited).unwrap(),
+///     "{\n  a: { b: { c: { d: 1 } } }\n}",
+/// );
+///
+/// let array = context.evaluate_script("[0, 1, 2, 3, 4]", 1).unwrap();
+/// let short = InspectOptions {
+///     max_array_len: Some(3),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     array.inspect(&context, short).unwrap(),
+///     "[ 0, 1, 2, ... 2 more items ]",
+/// );
+///
+/// let object = context.evaluate_script("({ n: 1, s: 'a' })", 1).unwrap();
+/// let colored = InspectOptions {
+///     colors: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     object.inspect(&context, colored).unwrap(),
+///     "{ n: \x1b[33m1\x1b[39m, s: \x1b[32m'a'\x1b[39m }",
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InspectOptions {
+    /// How many levels of nested objects are formatted, `None` for no limit.
+    pub depth: Option<usize>,
+    /// Whether to style the output with ANSI color codes.
+    pub colors: bool,
+    /// How many elements of arrays, typed arrays, array buffers, maps and
+    /// sets are formatted, `None` for no limit.
+    pub max_array_len: Option<usize>,
+}
+
+impl Default for InspectOptions {
+    /// The defaults of Node.js: a depth of 2, no colors and 100 elements.
+    fn default() -> Self {
+        Self {
+            depth: Some(2),
+            colors: false,
+            max_array_len: Some(100),
+        }
+    }
+}
+
+impl JSValue {
+    /// Formats this value for humans, like `util.inspect` of Node.js.
+    ///
+    /// Objects, arrays, functions, classes and their instances, errors,
+    /// dates, regular expressions, typed arrays, array buffers, maps, sets
+    /// and symbols are rendered like Node.js does, and cyclic references are
+    /// marked.
+    ///
+    /// The accessor properties of objects are shown as `[Getter]`,
+    /// `[Setter]` or `[Getter/Setter]` without being called. Array elements,
+    /// the `constructor` of prototypes, the sizes of maps and sets and the
+    /// methods of dates and regular expressions are read like any other
+    /// property though, so a getter, a proxy or an overridden method can run
+    /// while a value is inspected.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{InspectOptions, JSContext};
+    ///
+    /// let mut context = JSContext::default();
+    /// let inspect = |context: &mut JSContext, script: &str| {
+    ///     let value = context.evaluate_script(script, 1).unwrap();
+    ///     value.inspect(context, InspectOptions::default()).unwrap()
+    /// };
+    /// assert_eq!(
+    ///     inspect(
+    ///         &mut context,
+    ///         "const a = { list: [1, 'two'], map: new Map([[1, 2]]) }; a.self = a; a",
+    ///     ),
+    ///     "<ref *1> { list: [ 1, 'two' ], map: Map(1) { 1 => 2 }, self: [Circular *1] }",
+    /// );
+    /// assert_eq!(
+    ///     inspect(&mut context, "class A {}; class B extends A {}; [B, new B()]"),
+    ///     "[ [class B extends A], B {} ]",
+    /// );
+    /// assert_eq!(
+    ///     inspect(&mut context, "new Uint8Array([1, 2, 3])"),
+    ///     "Uint8Array(3) [ 1, 2, 3 ]",
+    /// );
+    /// assert_eq!(inspect(&mut context, "new Set([1, 'a'])"), "Set(2) { 1, 'a' }");
+    /// assert_eq!(
+    ///     inspect(&mut context, "({ [Symbol('id')]: 1, s: Symbol.iterator })"),
+    ///     "{ s: Symbol(Symbol.iterator), [Symbol(id)]: 1 }",
+    /// );
+    /// assert_eq!(
+    ///     inspect(
+    ///         &mut context,
+    ///         r#"({ 'a-b': "it's", b: 'line\n', c: 'both \' and "' })"#,
+    ///     ),
+    ///     r#"{ 'a-b': "it's", b: 'line\n', c: `both ' and "` }"#,
+    /// );
+    /// assert_eq!(
+    ///     inspect(&mut context, "Object.assign(Object.create(null), { [Symbol('id')]: 1 })"),
+    ///     "[Object: null prototype] { [Symbol(id)]: 1 }",
+    /// );
+    /// assert_eq!(
+    ///     inspect(&mut context, "({ [Symbol.toStringTag]: 'Map', size: 1 })"),
+    ///     "{ size: 1, [Symbol(Symbol.toStringTag)]: 'Map' }",
+    /// );
+    /// ```
+    pub fn inspect(
+        &self,
+        context: &JSContext,
+        options: InspectOptions,
+    ) -> Result<String, JSException> {
+        Inspector::new(context, options).inspect(self)
+    }
+
+    /// Returns an adapter formatting this value with `inspect` and the
+    /// default options.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let value = context.evaluate_script("[1, 'two']", 1).unwrap();
+    /// println!("{}", value.display(&context));
+    /// ```
+    pub fn display<'a>(&'a self, context: &'a JSContext) -> InspectDisplay<'a> {
+        InspectDisplay {
+            value: self,
+            context,
+            options: InspectOptions::default(),
+        }
+    }
+}
+
+/// Formats a value with `JSValue::inspect`, created by `JSValue::display`.
+///
+/// `Display` writes strings as is, like `console.log`, while `Debug` quotes
+/// them. A value that can't be inspected, e.g. a proxy that throws, is
+/// rendered as the thrown exception.
+#[derive(Clone, Copy)]
+pub struct InspectDisplay<'a> {
+    value: &'a JSValue,
+    context: &'a JSContext,
+    options: InspectOptions,
+}
+
+impl InspectDisplay<'_> {
+    /// Sets the options of `JSValue::inspect`.
+    pub fn options(mut self, options: InspectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        result: Result<String, JSException>,
+    ) -> fmt::Result {
+        match result {
+            Ok(formatted) => f.write_str(&formatted),
+            Err(exception) => write!(
+                f,
+                "<exception while inspecting: {}>",
+                exception.describe(self.context)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for InspectDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, display(self.context, self.value, self.options))
+    }
+}
+
+impl fmt::Debug for InspectDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, self.value.inspect(self.context, self.options))
+    }
+}
+
+/// The styles of the parts of the output, with the colors of Node.js.
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Number,
+    String,
+    Boolean,
+    Undefined,
+    Null,
+    Symbol,
+    Date,
+    RegExp,
+    /// Functions, references and placeholders.
+    Special,
+}
+
+impl Style {
+    /// Returns the ANSI codes setting and resetting the style.
+    fn codes(self) -> (u8, u8) {
+        match self {
+            Style::Number | Style::Boolean => (33, 39),
+            Style::String | Style::Symbol => (32, 39),
+            Style::Undefined => (90, 39),
+            Style::Null => (1, 22),
+            Style::Date => (35, 39),
+            Style::RegExp => (31, 39),
+            Style::Special => (36, 39),
+        }
+    }
+}
+
+/// Formats values, keeping track of the objects being formatted to detect
+/// cycles.
+pub(crate) struct Inspector<'a> {
+    context: &'a JSContext,
+    options: InspectOptions,
+    /// The objects being formatted, from the outermost one.
+    stack: Vec<JSValueIdentity>,
+    /// The reference numbers of the objects that are part of a cycle.
+    references: HashMap<JSValueIdentity, usize>,
+    /// The level of the last object whose entries were formatted, to know how
+    /// deeply nested the entries of an object are.
+    current_level: usize,
+}
+
+impl<'a> Inspector<'a> {
+    pub(crate) fn new(context: &'a JSContext, options: InspectOptions) -> Self {
+        Self {
+            context,
+            options,
+            stack: vec![],
+            references: HashMap::new(),
+            current_level: 0,
+        }
+    }
+
+    /// Formats a value, quoting strings.
+    pub(crate) fn inspect(&mut self, value: &JSValue) -> Result<String, JSException> {
+        self.format(value, 0)
+    }
+
+    fn format(&mut self, value: &JSValue, level: usize) -> Result<String, JSException> {
+        let kind = value.kind(self.context);
+        if !kind.is_object() {
+            return self.format_primitive(value, kind);
+        }
+        let identity = JSValueIdentity::from(value.clone());
+        if self.stack.contains(&identity) {
+            let next = self.references.len() + 1;
+            let reference = *self.references.entry(identity).or_insert(next);
+            return Ok(self.stylize(format!("[Circular *{reference}]"), Style::Special));
+        }
+
+        let object = value.to_object(self.context)?;
+        self.stack.push(identity.clone());
+        let formatted = self.format_object(&object, kind, level);
+        self.stack.pop();
+        let formatted = formatted?;
+        Ok(match self.references.get(&identity) {
+            Some(reference) => {
+                let reference = self.stylize(format!("<ref *{reference}>"), Style::Special);
+                format!("{reference} {formatted}")
+            }
+            None => formatted,
+        })
+    }
+
+    fn format_primitive(&self, value: &JSValue, kind: JSValueKind) -> Result<String, JSException> {
+        let context = self.context;
+        Ok(match kind {
+            JSValueKind::Undefined => self.stylize("undefined", Style::Undefined),
+            JSValueKind::Null => self.stylize("null", Style::Null),
+            JSValueKind::Boolean => {
+                self.stylize(value.to_bool(context).to_string(), Style::Boolean)
+            }
+            JSValueKind::Number => {
+                let number = format_number(context, value.to_number(context)?)?;
+                self.stylize(number, Style::Number)
+            }
+            JSValueKind::String => {
+                let string = value.to_js_string(context)?.to_string_lossy();
+                self.stylize(quote(&string), Style::String)
+            }
+            JSValueKind::BigInt => {
+                let bigint = format!("{}n", string_of(context, value)?);
+                self.stylize(bigint, Style::Number)
+            }
+            _ => self.stylize(string_of(context, value)?, Style::Symbol),
+        })
+    }
+
+    fn format_object(
+        &mut self,
+        object: &JSObject,
+        kind: JSValueKind,
+        level: usize,
+    ) -> Result<String, JSException> {
+        let context = self.context;
+        let constructor = self.constructor_name(object)?;
+        match kind {
+            JSValueKind::Function => {
+                let base = self.format_function(object, constructor.as_deref())?;
+                let keys = self.property_keys(object)?;
+                if keys.is_empty() || self.is_too_deep(level) {
+                    return Ok(base);
+                }
+                self.current_level = level;
+                let entries = self.property_entries(object, keys, level)?;
+                return Ok(self.reduce(format!("{base} {{"), entries, "}", level, None));
+            }
+            JSValueKind::Error => return self.format_error(object),
+            JSValueKind::Date => {
+                let date = object
+                    .call_method::<String>(context, "toISOString", ())
+                    .unwrap_or_else(|_| "Invalid Date".to_string());
+                return Ok(self.stylize(date, Style::Date));
+            }
+            _ => {}
+        }
+        let builtins = builtins::builtins(context)?;
+        let brand = match kind {
+            JSValueKind::Object => builtins.brand(context, &JSValue::from(object.inner)),
+            _ => None,
+        };
+        if brand == Some(Brand::RegExp) {
+            let regexp = builtins.call(
+                context,
+                &builtins.regexp_to_string,
+                &JSValue::from(object.inner),
+                &[],
+            )?;
+            let regexp = regexp.to_js_string(context)?.to_string_lossy();
+            return Ok(self.stylize(regexp, Style::RegExp));
+        }
+        if self.is_too_deep(level) {
+            let name = match kind {
+                JSValueKind::Array => "Array".to_string(),
+                _ => constructor.unwrap_or_else(|| "Object".to_string()),
+            };
+            return Ok(self.stylize(format!("[{name}]"), Style::Special));
+        }
+
+        self.current_level = level;
+        let class = constructor.as_deref().unwrap_or("[Object: null prototype]");
+        match kind {
+            JSValueKind::Array => {
+                let length = length(context, object)?;
+                let open = match class {
+                    "Array" => "[".to_string(),
+                    class => format!("{class}({length}) ["),
+                };
+                let (entries, numeric) = self.array_entries(object, length, level)?;
+                Ok(self.reduce(open, entries, "]", level, Some(numeric)))
+            }
+            JSValueKind::TypedArray(typed_array_type) => {
+                let length = length(context, object)?;
+                let open = format!("{}({length}) [", typed_array_type.name());
+                let (entries, _) = self.array_entries(object, length, level)?;
+                Ok(self.reduce(open, entries, "]", level, Some(true)))
+            }
+            JSValueKind::ArrayBuffer => {
+                let entries = self.array_buffer_entries(object)?;
+                Ok(self.reduce("ArrayBuffer {".to_string(), entries, "}", level, None))
+            }
+            JSValueKind::Promise => {
+                let mut entries = vec![self.stylize("<state unknown>", Style::Special)];
+                let keys = self.property_keys(object)?;
+                entries.extend(self.property_entries(object, keys, level)?);
+                Ok(self.reduce(format!("{class} {{"), entries, "}", level, None))
+            }
+            _ if brand == Some(Brand::Map) => {
+                let size = self.size(object, |builtins| &builtins.map_size)?;
+                let entries = self.map_entries(object, size, level)?;
+                Ok(self.reduce(format!("{class}({size}) {{"), entries, "}", level, None))
+            }
+            _ if brand == Some(Brand::Set) => {
+                let size = self.size(object, |builtins| &builtins.set_size)?;
+                let (entries, numeric) = self.set_entries(object, size, level)?;
+                let open = format!("{class}({size}) {{");
+                Ok(self.reduce(open, entries, "}", level, Some(numeric)))
+            }
+            _ if matches!(brand, Some(Brand::WeakMap | Brand::WeakSet)) => {
+                let entries = vec![self.stylize("<items unknown>", Style::Special)];
+                Ok(self.reduce(format!("{class} {{"), entries, "}", level, None))
+            }
+            _ => {
+                let open = match class {
+                    "Object" => "{".to_string(),
+                    class => format!("{class} {{"),
+                };
+                let keys = self.property_keys(object)?;
+                let entries = self.property_entries(object, keys, level)?;
+                Ok(self.reduce(open, entries, "}", level, None))
+            }
+        }
+    }
+
+    fn is_too_deep(&self, level: usize) -> bool {
+        self.options.depth.is_some_and(|depth| level > depth)
+    }
+
+    /// Returns the name of the constructor of an object, or `None` if the
+    /// object has a `null` prototype.
+    fn constructor_name(&self, object: &JSObject) -> Result<Option<String>, JSException> {
+        let context = self.context;
+        let prototype = object.get_prototype(context);
+        if !prototype.is_object(context) {
+            return Ok(None);
+        }
+        let constructor = prototype
+            .to_object(context)?
+            .get_property(context, "constructor")?;
+        let name = match constructor.kind(context) {
+            JSValueKind::Function => function_name(context, &constructor.to_object(context)?)?,
+            _ => String::new(),
+        };
+        Ok(Some(if name.is_empty() {
+            "Object".to_string()
+        } else {
+            name
+        }))
+    }
+
+    fn format_function(
+        &self,
+        function: &JSObject,
+        constructor: Option<&str>,
+    ) -> Result<String, JSException> {
+        let context = self.context;
+        let name = function_name(context, function)?;
+        let source = context
+            .get_global_object()
+            .get_property(context, "Function")?
+            .to_object(context)?
+            .get_property(context, "prototype")?
+            .to_object(context)?
+            .get_property(context, "toString")?
+            .to_object(context)?
+            .call::<String>(context, JSValue::from(function.inner), ())?;
+        let formatted = if source.starts_with("class") {
+            let mut formatted = match name.as_str() {
+                "" => "[class (anonymous)".to_string(),
+                name => format!("[class {name}"),
+            };
+            let parent = function.get_prototype(context);
+            if parent.kind(context) == JSValueKind::Function {
+                let parent = function_name(context, &parent.to_object(context)?)?;
+                if !parent.is_empty() {
+                    formatted.push_str(&format!(" extends {parent}"));
+                }
+            }
+            formatted.push(']');
+            formatted
+        } else {
+            // The constructor tells apart async and generator functions.
+            let kind = constructor.unwrap_or("Function");
+            match name.as_str() {
+                "" => format!("[{kind} (anonymous)]"),
+                name => format!("[{kind}: {name}]"),
+            }
+        };
+        Ok(self.stylize(formatted, Style::Special))
+    }
+
+    fn format_error(&self, error: &JSObject) -> Result<String, JSException> {
+        let context = self.context;
+        let string_property = |name| -> Result<Option<String>, JSException> {
+            let value = error.get_property(context, name)?;
+            Ok(if value.is_string(context) {
+                Some(value.to_js_string(context)?.to_string_lossy())
+            } else {
+                None
+            })
+        };
+        let name = string_property("name")?.unwrap_or_else(|| "Error".to_string());
+        let header = match string_property("message")? {
+            Some(message) if !message.is_empty() => format!("{name}: {message}"),
+            _ => name,
+        };
+        // Unlike V8, JavaScriptCore doesn't include the message in the stack.
+        Ok(match string_property("stack")? {
+            Some(stack) if !stack.is_empty() => {
+                let frames: Vec<_> = stack
+                    .lines()
+                    .map(|frame| format!("    at {frame}"))
+                    .collect();
+                format!("{header}\n{}", frames.join("\n"))
+            }
+            _ => format!("[{header}]"),
+        })
+    }
+
+    /// Formats the elements of an array-like object, telling if they are all
+    /// numbers.
+    fn array_entries(
+        &mut self,
+        array: &JSObject,
+        length: usize,
+        level: usize,
+    ) -> Result<(Vec<String>, bool), JSException> {
+        let context = self.context;
+        let shown = self.limit(length);
+        let mut entries = Vec::with_capacity(shown + 1);
+        let mut numeric = true;
+        for index in 0..shown {
+            let element = array.get_property_at_index(context, index as u32)?;
+            numeric &= matches!(
+                element.kind(context),
+                JSValueKind::Number | JSValueKind::BigInt
+            );
+            entries.push(self.format(&element, level + 1)?);
+        }
+        if shown < length {
+            entries.push(more_items(length - shown, "item"));
+        }
+        Ok((entries, numeric))
+    }
+
+    fn array_buffer_entries(&self, buffer: &JSObject) -> Result<Vec<String>, JSException> {
+        let context = self.context;
+        let bytes = buffer.get_array_buffer(context)?;
+        let shown = self.limit(bytes.len());
+        let mut contents: Vec<_> = bytes[..shown]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        if shown < bytes.len() {
+            contents.push(more_items(bytes.len() - shown, "byte"));
+        }
+        let byte_length = self.stylize(bytes.len().to_string(), Style::Number);
+        Ok(vec![
+            format!(
+                "{}: <{}>",
+                self.stylize("[Uint8Contents]", Style::Special),
+                contents.join(" ")
+            ),
+            format!("byteLength: {byte_length}"),
+        ])
+    }
+
+    fn map_entries(
+        &mut self,
+        map: &JSObject,
+        size: usize,
+        level: usize,
+    ) -> Result<Vec<String>, JSException> {
+        let context = self.context;
+        let shown = self.limit(size);
+        let mut entries = Vec::with_capacity(shown + 1);
+        let map = JSMap::from_object(context, map.clone())?;
+        for entry in map.entries(context)?.take(shown) {
+            let (key, value) = entry?;
+            let key = self.format(&key, level + 1)?;
+            let value = self.format(&value, level + 1)?;
+            entries.push(format!("{key} => {value}"));
+        }
+        if shown < size {
+            entries.push(more_items(size - shown, "item"));
+        }
+        Ok(entries)
+    }
+
+    fn set_entries(
+        &mut self,
+        set: &JSObject,
+        size: usize,
+        level: usize,
+    ) -> Result<(Vec<String>, bool), JSException> {
+        let context = self.context;
+        let shown = self.limit(size);
+        let mut entries = Vec::with_capacity(shown + 1);
+        let mut numeric = true;
+        let set = JSSet::from_object(context, set.clone())?;
+        for element in set.values(context)?.take(shown) {
+            let element = element?;
+            numeric &= matches!(
+                element.kind(context),
+                JSValueKind::Number | JSValueKind::BigInt
+            );
+            entries.push(self.format(&element, level + 1)?);
+        }
+        if shown < size {
+            entries.push(more_items(size - shown, "item"));
+        }
+        Ok((entries, numeric))
+    }
+
+    /// Returns the own enumerable keys of an object, strings then symbols.
+    fn property_keys(&self, object: &JSObject) -> Result<Vec<JSValue>, JSException> {
+        let context = self.context;
+        let target = JSValue::from(object.inner);
+        let builtins = builtins::builtins(context)?;
+        let mut keys: Vec<JSValue> = self.call_object(
+            |builtins| &builtins.object_keys,
+            std::slice::from_ref(&target),
+        )?;
+        let symbols: Vec<JSValue> = self.call_object(
+            |builtins| &builtins.object_get_own_property_symbols,
+            std::slice::from_ref(&target),
+        )?;
+        for symbol in symbols {
+            let enumerable = builtins.call(
+                context,
+                &builtins.object_property_is_enumerable,
+                &target,
+                std::slice::from_ref(&symbol),
+            )?;
+            if enumerable.to_bool(context) {
+                keys.push(symbol);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Formats the given properties as `key: value` entries, without calling
+    /// the getters.
+    fn property_entries(
+        &mut self,
+        object: &JSObject,
+        keys: Vec<JSValue>,
+        level: usize,
+    ) -> Result<Vec<String>, JSException> {
+        let context = self.context;
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            let descriptor: JSValue = self.call_object(
+                |builtins| &builtins.object_get_own_property_descriptor,
+                &[JSValue::from(object.inner), key.clone()],
+            )?;
+            if !descriptor.is_object(context) {
+                continue;
+            }
+            let descriptor = descriptor.to_object(context)?;
+            let key = if key.is_string(context) {
+                let key = key.to_js_string(context)?.to_string_lossy();
+                match format_key(&key) {
+                    quoted if quoted != key => self.stylize(quoted, Style::String),
+                    key => key,
+                }
+            } else {
+                format!(
+                    "[{}]",
+                    self.stylize(string_of(context, &key)?, Style::Symbol)
+                )
+            };
+            let getter = !descriptor
+                .get_property(context, "get")?
+                .is_undefined(context);
+            let setter = !descriptor
+                .get_property(context, "set")?
+                .is_undefined(context);
+            let value = match (getter, setter) {
+                (true, true) => self.stylize("[Getter/Setter]", Style::Special),
+                (true, false) => self.stylize("[Getter]", Style::Special),
+                (false, true) => self.stylize("[Setter]", Style::Special),
+                (false, false) => {
+                    let value = descriptor.get_property(context, "value")?;
+                    self.format(&value, level + 1)?
+                }
+            };
+            entries.push(format!("{key}: {value}"));
+        }
+        Ok(entries)
+    }
+
+    /// Calls a captured static method of `Object`.
+    fn call_object<R: FromJSValue>(
+        &self,
+        method: fn(&Builtins) -> &JSValue,
+        args: &[JSValue],
+    ) -> Result<R, JSException> {
+        let context = self.context;
+        let builtins = builtins::builtins(context)?;
+        let result = builtins.call(context, method(&builtins), &builtins.object, args)?;
+        R::from_js_value(context, &result)
+    }
+
+    /// Returns the size of a `Map` or a `Set` with its captured getter.
+    fn size(
+        &self,
+        object: &JSObject,
+        getter: fn(&Builtins) -> &JSValue,
+    ) -> Result<usize, JSException> {
+        let context = self.context;
+        let builtins = builtins::builtins(context)?;
+        let size = builtins.call(
+            context,
+            getter(&builtins),
+            &JSValue::from(object.inner),
+            &[],
+        )?;
+        Ok(size.to_number(context)? as usize)
+    }
+
+    /// Returns how many of `length` elements are formatted.
+    fn limit(&self, length: usize) -> usize {
+        self.options
+            .max_array_len
+            .map_or(length, |max_array_len| length.min(max_array_len))
+    }
+
+    fn stylize(&self, text: impl Into<String>, style: Style) -> String {
+        let text = text.into();
+        if !self.options.colors {
+            return text;
+        }
+        let (set, reset) = style.codes();
+        format!("\x1b[{set}m{text}\x1b[{reset}m")
+    }
+
+    /// Joins the entries of an object between its braces, on one line if
+    /// they are short and not too deeply nested, or one entry per line
+    /// otherwise. The elements of arrays and sets are grouped in columns
+    /// when there are many of them, `numeric` telling if they are all
+    /// numbers.
+    fn reduce(
+        &self,
+        open: String,
+        mut entries: Vec<String>,
+        close: &str,
+        level: usize,
+        numeric: Option<bool>,
+    ) -> String {
+        if entries.is_empty() {
+            return format!("{open}{close}");
+        }
+        let count = entries.len();
+        if let Some(numeric) = numeric {
+            if count > 6 {
+                entries = self.group_elements(entries, numeric, level);
+            }
+        }
+        if self.current_level.saturating_sub(level) < COMPACT && entries.len() == count {
+            let start = count + level * 2 + visible_len(&open) + 10;
+            let mut length = count + start;
+            let fits = length + count <= BREAK_LENGTH
+                && entries.iter().all(|entry| {
+                    length += visible_len(entry);
+                    length <= BREAK_LENGTH
+                });
+            if fits && entries.iter().all(|entry| !entry.contains('\n')) {
+                return format!("{open} {} {close}", entries.join(", "));
+            }
+        }
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| format!("  {}", entry.replace('\n', "\n  ")))
+            .collect();
+        format!("{open}\n{}\n{close}", entries.join(",\n"))
+    }
+
+    /// Groups many short elements in aligned columns, like Node.js.
+    fn group_elements(&self, entries: Vec<String>, numeric: bool, level: usize) -> Vec<String> {
+        let indentation = level * 2;
+        // The "more items" entry stays on its own line.
+        let count = match entries.last() {
+            Some(last) if last.starts_with("... ") => entries.len() - 1,
+            _ => entries.len(),
+        };
+        if count == 0 || entries[..count].iter().any(|entry| entry.contains('\n')) {
+            return entries;
+        }
+        let lengths: Vec<usize> = entries[..count]
+            .iter()
+            .map(|entry| visible_len(entry))
+            .collect();
+        let separator = 2;
+        let total_length: usize = lengths.iter().map(|length| length + separator).sum();
+        let max_length = lengths.iter().copied().max().unwrap_or(0);
+        let actual_max = max_length + separator;
+        // Only group if at least three elements fit on a line and the elements
+        // have similar lengths.
+        if actual_max * 3 + indentation >= BREAK_LENGTH
+            || (total_length as f64 / actual_max as f64 <= 5.0 && max_length > 6)
+        {
+            return entries;
+        }
+        let average_bias = (actual_max as f64 - total_length as f64 / entries.len() as f64).sqrt();
+        let biased_max = (actual_max as f64 - 3.0 - average_bias).max(1.0);
+        let columns = [
+            ((2.5 * biased_max * count as f64).sqrt() / biased_max).round() as usize,
+            (BREAK_LENGTH - indentation) / actual_max,
+            COMPACT * 4,
+            15,
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or(1);
+        if columns <= 1 {
+            return entries;
+        }
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                lengths
+                    .iter()
+                    .skip(column)
+                    .step_by(columns)
+                    .copied()
+                    .max()
+                    .unwrap_or(0)
+                    + separator
+            })
+            .collect();
+        let mut grouped = vec![];
+        for start in (0..count).step_by(columns) {
+            let end = (start + columns).min(count);
+            let mut line = String::new();
+            for index in start..end {
+                let last = index == end - 1;
+                let entry = if last {
+                    entries[index].clone()
+                } else {
+                    format!("{}, ", entries[index])
+                };
+                let length = lengths[index] + if last { 0 } else { separator };
+                let width = widths[index - start] - if last { separator } else { 0 };
+                let padding = " ".repeat(width.saturating_sub(length));
+                if numeric {
+                    line.push_str(&padding);
+                    line.push_str(&entry);
+                } else {
+                    line.push_str(&entry);
+                    if !last {
+                        line.push_str(&padding);
+                    }
+                }
+            }
+            grouped.push(line);
+        }
+        grouped.extend(entries.into_iter().skip(count));
+        grouped
+    }
+}
+
+/// Formats a value like `console.log` does, strings being written as is.
+pub(crate) fn display(
+    context: &JSContext,
+    value: &JSValue,
+    options: InspectOptions,
+) -> Result<String, JSException> {
+    if value.is_string(context) {
+        return Ok(value.to_js_string(context)?.to_string_lossy());
+    }
+    Inspector::new(context, options).inspect(value)
+}
+
+/// Returns the own enumerable string keys of an object, like `Object.keys`.
+pub(crate) fn own_keys(context: &JSContext, object: &JSObject) -> Result<Vec<String>, JSException> {
+    context
+        .get_global_object()
+        .get_property(context, "Object")?
+        .to_object(context)?
+        .call_method::<Vec<String>>(context, "keys", (JSValue::from(object.inner),))
+}
+
+/// Converts a value with the global `String` function, which unlike
+/// `JSValue::to_js_string` accepts symbols.
+pub(crate) fn string_of(context: &JSContext, value: &JSValue) -> Result<String, JSException> {
+    context
+        .get_global_object()
+        .get_property(context, "String")?
+        .to_object(context)?
+        .call::<String>(context, (), (value.clone(),))
+}
+
+/// Formats a number like JavaScript does, except for `-0`.
+pub(crate) fn format_number(context: &JSContext, number: f64) -> Result<String, JSException> {
+    if number == 0.0 && number.is_sign_negative() {
+        return Ok("-0".to_string());
+    }
+    Ok(JSValue::number(context, number)
+        .to_js_string(context)?
+        .to_string_lossy())
+}
+
+fn function_name(context: &JSContext, function: &JSObject) -> Result<String, JSException> {
+    let name = function.get_property(context, "name")?;
+    Ok(if name.is_string(context) {
+        name.to_js_string(context)?.to_string_lossy()
+    } else {
+        String::new()
+    })
+}
+
+fn length(context: &JSContext, object: &JSObject) -> Result<usize, JSException> {
+    Ok(object.get_property(context, "length")?.to_number(context)? as usize)
+}
+
+fn more_items(count: usize, noun: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("... {count} more {noun}{plural}")
+}
+
+/// Returns the length of a string without its ANSI escape sequences.
+fn visible_len(string: &str) -> usize {
+    let mut length = 0;
+    let mut escaped = false;
+    for c in string.chars() {
+        match c {
+            '\x1b' => escaped = true,
+            'm' if escaped => escaped = false,
+            _ if escaped => {}
+            _ => length += 1,
+        }
+    }
+    length
+}
+
+/// Quotes a string like Node.js, with single quotes unless the string
+/// contains some.
+pub(crate) fn quote(string: &str) -> String {
+    let quote = if !string.contains('\'') {
+        '\''
+    } else if !string.contains('"') {
+        '"'
+    } else if !string.contains('`') && !string.contains("${") {
+        '`'
+    } else {
+        '\''
+    };
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push(quote);
+    for c in string.chars() {
+        match c {
+            c if c == quote => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\x08' => quoted.push_str("\\b"),
+            '\x0c' => quoted.push_str("\\f"),
+            '\x0b' => quoted.push_str("\\v"),
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push(quote);
+    quoted
+}
+
+/// Formats a property key, quoting it unless it's an identifier.
+pub(crate) fn format_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
@@ -1,7 +1,9 @@
 use rusty_jsc_sys::*;
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     ffi::{CString, NulError},
-    string::FromUtf8Error,
+    string::{FromUtf16Error, FromUtf8Error},
 };
 
 /// A JavaScript string.
@@ -17,9 +19,18 @@ impl Drop for JSString {
     }
 }
 
+impl Clone for JSString {
+    fn clone(&self) -> Self {
+        Self {
+            inner: unsafe { JSStringRetain(self.inner) },
+        }
+    }
+}
+
 impl std::fmt::Display for JSString {
+    /// Formats the string, replacing lone surrogates with `U+FFFD`.
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        let res = self.to_string_utf8().unwrap(); // TODO: return format error
+        let res = self.to_string_lossy();
         write!(fmt, "{res}")
     }
 }
@@ -44,23 +55,100 @@ impl JSString {
         String::from_utf8(chars[0..(len - 1) as usize].to_vec())
     }
 
+    /// Returns the `JSString` as a Rust `String`, failing if the string
+    /// contains lone surrogates.
+    pub fn try_to_string(&self) -> Result<String, FromUtf16Error> {
+        String::from_utf16(self.as_utf16())
+    }
+
+    /// Returns the `JSString` as a Rust `String`, replacing lone surrogates
+    /// with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_utf16())
+    }
+
+    /// Returns the UTF-16 code units of the string, without copying them.
+    pub fn as_utf16(&self) -> &[u16] {
+        let len = self.len();
+        if len == 0 {
+            return &[];
+        }
+        // The characters are immutable and owned by the string, which we
+        // retain while the slice is borrowed.
+        unsafe { std::slice::from_raw_parts(JSStringGetCharactersPtr(self.inner), len) }
+    }
+
+    /// Returns the number of UTF-16 code units of the string.
+    pub fn len(&self) -> usize {
+        unsafe { JSStringGetLength(self.inner) as usize }
+    }
+
+    /// Checks if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Constructs a JSString from a Rust `String`
+    ///
+    /// This fails if the string contains a NUL character, see `from_str` to
+    /// support them.
     pub fn from_utf8(value: String) -> Result<Self, NulError> {
         let value = CString::new(value.as_bytes())?;
         let inner = unsafe { JSStringCreateWithUTF8CString(value.as_ptr()) };
         Ok(JSString { inner })
     }
+
+    /// Constructs a JSString from UTF-16 code units, which may contain NUL
+    /// characters and lone surrogates.
+    pub fn from_utf16(value: &[u16]) -> Self {
+        let inner = unsafe { JSStringCreateWithCharacters(value.as_ptr(), value.len() as _) };
+        JSString { inner }
+    }
+
+    /// Constructs a JSString from a Rust `str`, including any NUL character.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Self {
+        match CString::new(value) {
+            Ok(value) => JSString {
+                inner: unsafe { JSStringCreateWithUTF8CString(value.as_ptr()) },
+            },
+            Err(_) => Self::from_utf16(&value.encode_utf16().collect::<Vec<_>>()),
+        }
+    }
 }
 
 impl From<String> for JSString {
     fn from(value: String) -> JSString {
-        Self::from_utf8(value).unwrap()
+        Self::from_str(&value)
     }
 }
 
 impl From<&str> for JSString {
     fn from(value: &str) -> JSString {
-        Self::from_utf8(value.to_string()).unwrap()
+        Self::from_str(value)
+    }
+}
+
+impl PartialEq for JSString {
+    fn eq(&self, other: &JSString) -> bool {
+        unsafe { JSStringIsEqual(self.inner, other.inner) }
+    }
+}
+
+impl Eq for JSString {}
+
+impl PartialEq<str> for JSString {
+    fn eq(&self, other: &str) -> bool {
+        match CString::new(other) {
+            Ok(other) => unsafe { JSStringIsEqualToUTF8CString(self.inner, other.as_ptr()) },
+            Err(_) => self.as_utf16().iter().copied().eq(other.encode_utf16()),
+        }
+    }
+}
+
+impl PartialEq<&str> for JSString {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
     }
 }
 
@@ -74,3 +162,34 @@ impl std::fmt::Debug for JSString {
         write!(f, "JSString({})", self)
     }
 }
+
+thread_local! {
+    /// The interned strings of each context, keyed by the global context.
+    ///
+    /// A `JSString` doesn't belong to a context, so reusing the strings of a
+    /// destroyed context is harmless: the table is only cleaned up to release
+    /// memory.
+    static INTERNED_STRINGS: RefCell<HashMap<usize, HashMap<String, JSString>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Returns the interned `JSString` for `value` in the given context.
+pub(crate) fn intern(context: JSGlobalContextRef, value: &str) -> JSString {
+    INTERNED_STRINGS.with(|strings| {
+        let mut strings = strings.borrow_mut();
+        let strings = strings.entry(context as usize).or_default();
+        if let Some(string) = strings.get(value) {
+            return string.clone();
+        }
+        let string = JSString::from_str(value);
+        strings.insert(value.to_owned(), string.clone());
+        string
+    })
+}
+
+/// Releases the interned strings of the given context.
+pub(crate) fn forget_interned(context: JSGlobalContextRef) {
+    // The table may already be destroyed if the context is dropped while the
+    // thread exits.
+    let _ = INTERNED_STRINGS.try_with(|strings| strings.borrow_mut().remove(&(context as usize)));
+}
@@ -0,0 +1,243 @@
+use crate::{JSContext, JSException, JSObject, JSValue};
+use rusty_jsc_sys::*;
+
+impl<T> JSObject<T> {
+    /// Iterates over the elements of an array-like object, from index `0` to
+    /// `length - 1`.
+    ///
+    /// The length is read once, when the iterator is created.
+    pub fn array_iter<'a>(&self, context: &'a JSContext) -> Result<ArrayIter<'a>, JSException> {
+        let length = self.get_property(context, "length")?.to_number(context)?;
+        // Like the `ToLength` operation, `NaN` and negative lengths are 0 and
+        // the length is at most 2^53 - 1.
+        let length = if length.is_nan() {
+            0
+        } else {
+            length.clamp(0.0, 9_007_199_254_740_991.0) as u64
+        };
+        Ok(ArrayIter::new(context, JSObject::from(self.inner), length))
+    }
+
+    /// Iterates over an iterable object (an array, a `Map`, a `Set`, a
+    /// generator...) with the JavaScript iterator protocol, i.e. like a
+    /// `for...of` loop.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let set = context
+    ///     .evaluate_script("new Set([1, 2, 3])", 1)
+    ///     .unwrap()
+    ///     .to_object(&context)
+    ///     .unwrap();
+    /// let sum = set
+    ///     .iter(&context)
+    ///     .unwrap()
+    ///     .map(|value| value.unwrap().to_number(&context).unwrap())
+    ///     .sum::<f64>();
+    /// assert_eq!(sum, 6.0);
+    /// ```
+    pub fn iter<'a>(&self, context: &'a JSContext) -> Result<JSIterator<'a>, JSException> {
+        let symbol = JSValue::well_known_symbol(context, "iterator")?;
+        let method = self.get_property(context, symbol)?.to_object(context)?;
+        let this: JSObject = JSObject::from(self.inner);
+        let iterator = method
+            .call_as_function(context, Some(&this), &[])?
+            .to_object(context)?;
+        let next = iterator.get_property(context, "next")?.to_object(context)?;
+        Ok(JSIterator::new(context, iterator, next))
+    }
+
+    /// Iterates over the enumerable properties of an object as
+    /// `(name, value)` pairs, like a `for...in` loop.
+    ///
+    /// The property names are read once, when the iterator is created.
+    pub fn entries<'a>(&self, context: &'a JSContext) -> Entries<'a> {
+        let names = self.get_property_names(context).into_iter();
+        Entries::new(context, JSObject::from(self.inner), names)
+    }
+}
+
+/// An iterator over the elements of an array-like object, created by
+/// `JSObject::array_iter`.
+pub struct ArrayIter<'a> {
+    context: &'a JSContext,
+    /// The array is kept between calls to `next`, so it has to be protected
+    /// from garbage collection.
+    array: JSObject,
+    index: u64,
+    length: u64,
+}
+
+impl<'a> ArrayIter<'a> {
+    fn new(context: &'a JSContext, array: JSObject, length: u64) -> Self {
+        unsafe { JSValueProtect(context.inner, array.inner) };
+        Self {
+            context,
+            array,
+            index: 0,
+            length,
+        }
+    }
+}
+
+impl Iterator for ArrayIter<'_> {
+    type Item = Result<JSValue, JSException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.length {
+            return None;
+        }
+        let element = match u32::try_from(self.index) {
+            Ok(index) => self.array.get_property_at_index(self.context, index),
+            // Array-like objects can have indexes above the ones of arrays.
+            Err(_) => self
+                .array
+                .get_property(self.context, self.index.to_string()),
+        };
+        self.index += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.length - self.index;
+        match usize::try_from(remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl Drop for ArrayIter<'_> {
+    fn drop(&mut self) {
+        unsafe { JSValueUnprotect(self.context.inner, self.array.inner) };
+    }
+}
+
+/// An iterator driving the JavaScript iterator protocol, created by
+/// `JSObject::iter`.
+///
+/// The iteration stops after the first exception. If the iterator is dropped
+/// before the end, its `return` method is called to let the JavaScript side
+/// clean up, like when breaking out of a `for...of` loop.
+pub struct JSIterator<'a> {
+    context: &'a JSContext,
+    iterator: JSObject,
+    next: JSObject,
+    done: bool,
+}
+
+impl<'a> JSIterator<'a> {
+    pub(crate) fn new(context: &'a JSContext, iterator: JSObject, next: JSObject) -> Self {
+        // The iterator is kept between calls to `next`, so it has to be
+        // protected from garbage collection.
+        unsafe {
+            JSValueProtect(context.inner, iterator.inner);
+            JSValueProtect(context.inner, next.inner);
+        }
+        Self {
+            context,
+            iterator,
+            next,
+            done: false,
+        }
+    }
+
+    fn step(&mut self) -> Result<Option<JSValue>, JSException> {
+        let result = self
+            .next
+            .call_as_function(self.context, Some(&self.iterator), &[])?
+            .to_object(self.context)?;
+        if result
+            .get_property(self.context, "done")?
+            .to_bool(self.context)
+        {
+            return Ok(None);
+        }
+        result.get_property(self.context, "value").map(Some)
+    }
+}
+
+impl Iterator for JSIterator<'_> {
+    type Item = Result<JSValue, JSException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.step() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(exception) => {
+                self.done = true;
+                Some(Err(exception))
+            }
+        }
+    }
+}
+
+impl Drop for JSIterator<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            if let Ok(return_method) = self
+                .iterator
+                .get_property(self.context, "return")
+                .and_then(|method| method.to_object(self.context))
+            {
+                let _ = return_method.call_as_function(self.context, Some(&self.iterator), &[]);
+            }
+        }
+        unsafe {
+            JSValueUnprotect(self.context.inner, self.iterator.inner);
+            JSValueUnprotect(self.context.inner, self.next.inner);
+        }
+    }
+}
+
+/// An iterator over the `(name, value)` pairs of the enumerable properties of
+/// an object, created by `JSObject::entries`.
+pub struct Entries<'a> {
+    context: &'a JSContext,
+    /// The object is kept between calls to `next`, so it has to be protected
+    /// from garbage collection.
+    object: JSObject,
+    names: std::vec::IntoIter<String>,
+}
+
+impl<'a> Entries<'a> {
+    fn new(context: &'a JSContext, object: JSObject, names: std::vec::IntoIter<String>) -> Self {
+        unsafe { JSValueProtect(context.inner, object.inner) };
+        Self {
+            context,
+            object,
+            names,
+        }
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Result<(String, JSValue), JSException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        Some(
+            self.object
+                .get_property(self.context, name.as_str())
+                .map(|value| (name, value)),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+impl Drop for Entries<'_> {
+    fn drop(&mut self) {
+        unsafe { JSValueUnprotect(self.context.inner, self.object.inner) };
+    }
+}
@@ -0,0 +1,254 @@
+use crate::builtins::{self, Brand};
+use crate::{JSContext, JSObject, JSValue};
+use rusty_jsc_sys::*;
+
+/// The type of a `JSValue`, as returned by `JSValue::kind`.
+///
+/// Objects are refined into the most specific built-in type they are an
+/// instance of, so a value is either a primitive or one of the object kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JSValueKind {
+    Undefined,
+    Null,
+    Boolean,
+    Number,
+    String,
+    Symbol,
+    BigInt,
+    Array,
+    Date,
+    TypedArray(TypedArrayType),
+    ArrayBuffer,
+    Function,
+    Promise,
+    Error,
+    /// Any other object.
+    Object,
+}
+
+impl JSValueKind {
+    /// Checks if this kind is an object kind, i.e. not a primitive.
+    pub fn is_object(&self) -> bool {
+        !matches!(
+            self,
+            JSValueKind::Undefined
+                | JSValueKind::Null
+                | JSValueKind::Boolean
+                | JSValueKind::Number
+                | JSValueKind::String
+                | JSValueKind::Symbol
+                | JSValueKind::BigInt
+        )
+    }
+}
+
+/// The element type of a typed array, mapping `JSTypedArrayType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypedArrayType {
+    Int8Array,
+    Int16Array,
+    Int32Array,
+    Uint8Array,
+    Uint8ClampedArray,
+    Uint16Array,
+    Uint32Array,
+    Float32Array,
+    Float64Array,
+    BigInt64Array,
+    BigUint64Array,
+}
+
+impl TypedArrayType {
+    /// Converts a `JSTypedArrayType`, returning `None` for
+    /// `kJSTypedArrayTypeArrayBuffer` and `kJSTypedArrayTypeNone`.
+    pub fn from_raw(raw: JSTypedArrayType) -> Option<Self> {
+        #[allow(non_upper_case_globals)]
+        let typed_array_type = match raw {
+            JSTypedArrayType_kJSTypedArrayTypeInt8Array => TypedArrayType::Int8Array,
+            JSTypedArrayType_kJSTypedArrayTypeInt16Array => TypedArrayType::Int16Array,
+            JSTypedArrayType_kJSTypedArrayTypeInt32Array => TypedArrayType::Int32Array,
+            JSTypedArrayType_kJSTypedArrayTypeUint8Array => TypedArrayType::Uint8Array,
+            JSTypedArrayType_kJSTypedArrayTypeUint8ClampedArray => {
+                TypedArrayType::Uint8ClampedArray
+            }
+            JSTypedArrayType_kJSTypedArrayTypeUint16Array => TypedArrayType::Uint16Array,
+            JSTypedArrayType_kJSTypedArrayTypeUint32Array => TypedArrayType::Uint32Array,
+            JSTypedArrayType_kJSTypedArrayTypeFloat32Array => TypedArrayType::Float32Array,
+            JSTypedArrayType_kJSTypedArrayTypeFloat64Array => TypedArrayType::Float64Array,
+            JSTypedArrayType_kJSTypedArrayTypeBigInt64Array => TypedArrayType::BigInt64Array,
+            JSTypedArrayType_kJSTypedArrayTypeBigUint64Array => TypedArrayType::BigUint64Array,
+            _ => return None,
+        };
+        Some(typed_array_type)
+    }
+
+    /// Returns the matching `JSTypedArrayType`.
+    pub fn to_raw(self) -> JSTypedArrayType {
+        match self {
+            TypedArrayType::Int8Array => JSTypedArrayType_kJSTypedArrayTypeInt8Array,
+            TypedArrayType::Int16Array => JSTypedArrayType_kJSTypedArrayTypeInt16Array,
+            TypedArrayType::Int32Array => JSTypedArrayType_kJSTypedArrayTypeInt32Array,
+            TypedArrayType::Uint8Array => JSTypedArrayType_kJSTypedArrayTypeUint8Array,
+            TypedArrayType::Uint8ClampedArray => {
+                JSTypedArrayType_kJSTypedArrayTypeUint8ClampedArray
+            }
+            TypedArrayType::Uint16Array => JSTypedArrayType_kJSTypedArrayTypeUint16Array,
+            TypedArrayType::Uint32Array => JSTypedArrayType_kJSTypedArrayTypeUint32Array,
+            TypedArrayType::Float32Array => JSTypedArrayType_kJSTypedArrayTypeFloat32Array,
+            TypedArrayType::Float64Array => JSTypedArrayType_kJSTypedArrayTypeFloat64Array,
+            TypedArrayType::BigInt64Array => JSTypedArrayType_kJSTypedArrayTypeBigInt64Array,
+            TypedArrayType::BigUint64Array => JSTypedArrayType_kJSTypedArrayTypeBigUint64Array,
+        }
+    }
+
+    /// Returns the name of the typed array constructor, e.g. `Uint8Array`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TypedArrayType::Int8Array => "Int8Array",
+            TypedArrayType::Int16Array => "Int16Array",
+            TypedArrayType::Int32Array => "Int32Array",
+            TypedArrayType::Uint8Array => "Uint8Array",
+            TypedArrayType::Uint8ClampedArray => "Uint8ClampedArray",
+            TypedArrayType::Uint16Array => "Uint16Array",
+            TypedArrayType::Uint32Array => "Uint32Array",
+            TypedArrayType::Float32Array => "Float32Array",
+            TypedArrayType::Float64Array => "Float64Array",
+            TypedArrayType::BigInt64Array => "BigInt64Array",
+            TypedArrayType::BigUint64Array => "BigUint64Array",
+        }
+    }
+}
+
+/// A view of a `JSValue` that can be matched on, as returned by
+/// `JSValue::view`.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, ValueView};
+///
+/// let mut context = JSContext::default();
+/// let value = context.evaluate_script("[1, 2]", 1).unwrap();
+/// match value.view(&context) {
+///     ValueView::Number(number) => println!("number: {number}"),
+///     ValueView::String(string) => println!("string: {string}"),
+///     ValueView::Array(array) => println!("array: {:?}", array),
+///     _ => println!("something else"),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub enum ValueView {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Symbol(JSValue),
+    BigInt(JSValue),
+    Array(JSObject),
+    Date(JSObject),
+    TypedArray(TypedArrayType, JSObject),
+    ArrayBuffer(JSObject),
+    Function(JSObject),
+    Promise(JSObject),
+    Error(JSObject),
+    Object(JSObject),
+}
+
+impl JSValue {
+    /// Returns the type of this value.
+    ///
+    /// `Promise` and `Error` objects are recognized with the built-ins
+    /// captured when the context was created, so objects of other contexts
+    /// are recognized as well, and an ordinary object can't pass for one by
+    /// setting its `Symbol.toStringTag`. Recognizing a promise marks it as
+    /// handled, like calling its `then` method.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, JSValueKind};
+    ///
+    /// let mut context = JSContext::default();
+    /// let promise = context.evaluate_script("Promise.resolve(1)", 1).unwrap();
+    /// assert_eq!(promise.kind(&context), JSValueKind::Promise);
+    /// let spoofed = context
+    ///     .evaluate_script("({ [Symbol.toStringTag]: 'Promise' })", 1)
+    ///     .unwrap();
+    /// assert_eq!(spoofed.kind(&context), JSValueKind::Object);
+    /// ```
+    pub fn kind(&self, context: &JSContext) -> JSValueKind {
+        if let Some(kind) = self.primitive_kind(context) {
+            return kind;
+        }
+
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let typed_array_type =
+            unsafe { JSValueGetTypedArrayType(context.inner, self.inner, &mut exception) };
+        if typed_array_type == JSTypedArrayType_kJSTypedArrayTypeArrayBuffer {
+            return JSValueKind::ArrayBuffer;
+        }
+        if let Some(typed_array_type) = TypedArrayType::from_raw(typed_array_type) {
+            return JSValueKind::TypedArray(typed_array_type);
+        }
+        if self.is_array(context) {
+            return JSValueKind::Array;
+        }
+        if self.is_date(context) {
+            return JSValueKind::Date;
+        }
+        if unsafe { JSObjectIsFunction(context.inner, self.inner as _) } {
+            return JSValueKind::Function;
+        }
+        let brand = builtins::builtins(context)
+            .ok()
+            .and_then(|builtins| builtins.brand(context, self));
+        match brand {
+            Some(Brand::Promise) => JSValueKind::Promise,
+            Some(Brand::Error) => JSValueKind::Error,
+            _ => JSValueKind::Object,
+        }
+    }
+
+    /// Returns the type of this value if it's a primitive, or `None` if it's
+    /// an object.
+    pub(crate) fn primitive_kind(&self, context: &JSContext) -> Option<JSValueKind> {
+        #[allow(non_upper_case_globals)]
+        match unsafe { JSValueGetType(context.inner, self.inner) } {
+            JSType_kJSTypeUndefined => Some(JSValueKind::Undefined),
+            JSType_kJSTypeNull => Some(JSValueKind::Null),
+            JSType_kJSTypeBoolean => Some(JSValueKind::Boolean),
+            JSType_kJSTypeNumber => Some(JSValueKind::Number),
+            JSType_kJSTypeString => Some(JSValueKind::String),
+            JSType_kJSTypeSymbol => Some(JSValueKind::Symbol),
+            JSType_kJSTypeBigInt => Some(JSValueKind::BigInt),
+            // Versions without the BigInt API report BigInts as objects.
+            _ if !self.is_object(context) => Some(JSValueKind::BigInt),
+            _ => None,
+        }
+    }
+
+    /// Returns a view of this value that can be matched on.
+    pub fn view(&self, context: &JSContext) -> ValueView {
+        let object = || JSObject::from(self.inner as JSObjectRef);
+        match self.kind(context) {
+            JSValueKind::Undefined => ValueView::Undefined,
+            JSValueKind::Null => ValueView::Null,
+            JSValueKind::Boolean => ValueView::Boolean(self.to_bool(context)),
+            // Converting a number or a string primitive doesn't throw.
+            JSValueKind::Number => ValueView::Number(self.to_number(context).unwrap()),
+            JSValueKind::String => {
+                let string = self.to_js_string(context).unwrap();
+                ValueView::String(string.to_string_lossy())
+            }
+            JSValueKind::Symbol => ValueView::Symbol(self.clone()),
+            JSValueKind::BigInt => ValueView::BigInt(self.clone()),
+            JSValueKind::Array => ValueView::Array(object()),
+            JSValueKind::Date => ValueView::Date(object()),
+            JSValueKind::TypedArray(typed_array_type) => {
+                ValueView::TypedArray(typed_array_type, object())
+            }
+            JSValueKind::ArrayBuffer => ValueView::ArrayBuffer(object()),
+            JSValueKind::Function => ValueView::Function(object()),
+            JSValueKind::Promise => ValueView::Promise(object()),
+            JSValueKind::Error => ValueView::Error(object()),
+            JSValueKind::Object => ValueView::Object(object()),
+        }
+    }
+}
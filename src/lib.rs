@@ -23,24 +23,68 @@
 //! }
 //! ```
 
+mod bigint;
+mod builtins;
+mod call;
+mod clone;
+mod collections;
+mod console;
+mod convert;
+mod error;
+mod executor;
+mod inspect;
 mod internal;
-
-use std::panic;
-
+mod iter;
+mod kind;
+mod panic;
+mod persistent;
+mod vm;
+mod weak;
+mod worker;
+
+use std::any::Any;
+use std::cell::{Cell, OnceCell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+pub use crate::clone::{SerializedValue, StructuredClone};
+pub use crate::collections::{JSMap, JSSet, JSWeakMap, JSWeakRef};
+#[cfg(feature = "log")]
+pub use crate::console::LogSink;
+#[cfg(feature = "tracing")]
+pub use crate::console::TracingSink;
+pub use crate::console::{ConsoleLevel, ConsoleSink, StdioSink};
+pub use crate::convert::{FromJSValue, IntoJSArgs, IntoJSValue};
+pub use crate::error::{JSError, JSErrorKind};
+pub use crate::inspect::{InspectDisplay, InspectOptions};
 pub use crate::internal::JSString;
+pub use crate::iter::{ArrayIter, Entries, JSIterator};
+pub use crate::kind::{JSValueKind, TypedArrayType, ValueView};
+pub use crate::panic::{set_panic_logger, PanicPolicy};
+pub use crate::persistent::{Persistent, PersistentValue};
+pub use crate::vm::JSVirtualMachine;
+pub use crate::weak::{FinalizationRegistry, WeakJSObject};
+pub use crate::worker::{Worker, WorkerEvent};
 // #[macro_export]
 mod closure;
-pub use rusty_jsc_macros::callback;
+pub use rusty_jsc_macros::{callback, class_hook, constructor};
 pub use rusty_jsc_sys::JSObjectCallAsFunctionCallback;
 use rusty_jsc_sys::*;
 use std::fmt;
 pub mod private {
+    pub use crate::executor::spawn_promise;
+    pub use crate::panic::{handle_callback_panic, handle_constructor_panic, handle_hook_panic};
     pub use rusty_jsc_sys::*;
 }
 
 // pub use crate::closure::callback_closure;
 
 /// A JavaScript value.
+///
+/// The `Debug` output of a value only shows its reference, use
+/// `value.display(&context)` to format the value itself.
 #[derive(Debug, Clone)]
 pub struct JSValue {
     inner: JSValueRef,
@@ -120,6 +164,26 @@ impl JSValue {
         JSValue::from(unsafe { JSValueMakeString(context.inner, value.inner) })
     }
 
+    /// Creates a new unique `symbol` value with the given description.
+    pub fn symbol(context: &JSContext, description: impl Into<JSString>) -> JSValue {
+        let description: JSString = description.into();
+        JSValue::from(unsafe { JSValueMakeSymbol(context.inner, description.inner) })
+    }
+
+    /// Gets a well-known symbol such as `iterator` or `toStringTag`, i.e.
+    /// the value of `Symbol[name]`.
+    pub fn well_known_symbol(context: &JSContext, name: &str) -> Result<JSValue, JSException> {
+        let symbol = context
+            .get_global_object()
+            .get_property(context, "Symbol")?
+            .to_object(context)?
+            .get_property(context, name)?;
+        if !symbol.is_symbol(context) {
+            return Err(JSValue::string(context, format!("Symbol.{name} is not a symbol")).into());
+        }
+        Ok(symbol)
+    }
+
     /// Creates a function callback
     pub fn callback(context: &JSContext, callback: JSObjectCallAsFunctionCallback) -> JSValue {
         let name: JSString = "".into();
@@ -142,6 +206,11 @@ impl JSValue {
         unsafe { JSValueIsBoolean(context.inner, self.inner) }
     }
 
+    /// Checks if this value is an object.
+    pub fn is_object(&self, context: &JSContext) -> bool {
+        unsafe { JSValueIsObject(context.inner, self.inner) }
+    }
+
     /// Checks if this value is `Array`.
     pub fn is_array(&self, context: &JSContext) -> bool {
         unsafe { JSValueIsArray(context.inner, self.inner) }
@@ -167,37 +236,137 @@ impl JSValue {
         unsafe { JSValueIsSymbol(context.inner, self.inner) }
     }
 
+    /// Checks if this value is equal to another value, like the `==`
+    /// operator.
+    ///
+    /// The comparison may convert objects to primitives, which can run
+    /// script code and throw.
+    pub fn loose_eq(&self, context: &JSContext, other: &JSValue) -> Result<bool, JSException> {
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let result =
+            unsafe { JSValueIsEqual(context.inner, self.inner, other.inner, &mut exception) };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(result)
+    }
+
+    /// Checks if this value is strictly equal to another value, like the
+    /// `===` operator.
+    pub fn strict_eq(&self, context: &JSContext, other: &JSValue) -> bool {
+        unsafe { JSValueIsStrictEqual(context.inner, self.inner, other.inner) }
+    }
+
+    /// Checks if this value is the same value as another value, like
+    /// `Object.is`.
+    ///
+    /// This is the strict equality, except that `NaN` is the same value as
+    /// itself and that `+0` and `-0` are different values.
+    pub fn same_value(&self, context: &JSContext, other: &JSValue) -> bool {
+        if self.is_number(context) && other.is_number(context) {
+            // Converting a number primitive doesn't throw.
+            let a = self.to_number(context).unwrap();
+            let b = other.to_number(context).unwrap();
+            return (a.is_nan() && b.is_nan())
+                || (a == b && a.is_sign_negative() == b.is_sign_negative());
+        }
+        self.strict_eq(context, other)
+    }
+
+    /// Checks if this value is an instance of the given constructor, like
+    /// the `instanceof` operator.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let array = context.evaluate_script("[1, 2]", 1).unwrap();
+    /// let global = context.get_global_object();
+    /// let array_constructor = global.get_property(&context, "Array").unwrap();
+    /// let map_constructor = global.get_property(&context, "Map").unwrap();
+    /// let array_constructor = array_constructor.to_object(&context).unwrap();
+    /// let map_constructor = map_constructor.to_object(&context).unwrap();
+    /// assert!(array.instance_of(&context, &array_constructor).unwrap());
+    /// assert!(!array.instance_of(&context, &map_constructor).unwrap());
+    /// // Only callable objects can be on the right of `instanceof`.
+    /// let object = context.evaluate_script("({})", 1).unwrap();
+    /// let object = object.to_object(&context).unwrap();
+    /// assert!(array.instance_of(&context, &object).is_err());
+    /// ```
+    pub fn instance_of<T>(
+        &self,
+        context: &JSContext,
+        constructor: &JSObject<T>,
+    ) -> Result<bool, JSException> {
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let result = unsafe {
+            JSValueIsInstanceOfConstructor(
+                context.inner,
+                self.inner,
+                constructor.inner,
+                &mut exception,
+            )
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(result)
+    }
+
+    /// Checks if this value is an object created from the given class (or a
+    /// class deriving from it).
+    ///
+    /// Check this before reading the private data of `this` in a class
+    /// method: scripts can call the method with any object as `this`.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSClass, JSContext, JSObject, JSObjectGeneric, JSValue};
+    ///
+    /// let context = JSContext::default();
+    /// let class = JSClass::create("Point", None);
+    /// let other_class = JSClass::create("Size", None);
+    /// let point = JSValue::from(class.make_object(&context));
+    /// assert!(point.is_object_of_class(&context, &class));
+    /// assert!(!point.is_object_of_class(&context, &other_class));
+    /// let object = JSValue::from(JSObject::<JSObjectGeneric>::new(&context));
+    /// assert!(!object.is_object_of_class(&context, &class));
+    /// assert!(!JSValue::null(&context).is_object_of_class(&context, &class));
+    /// ```
+    pub fn is_object_of_class(&self, context: &JSContext, class: &JSClass) -> bool {
+        unsafe { JSValueIsObjectOfClass(context.inner, self.inner, class.inner) }
+    }
+
     /// Gets this value as a `bool`.
     pub fn to_bool(&self, context: &JSContext) -> bool {
         unsafe { JSValueToBoolean(context.inner, self.inner) }
     }
 
     /// Formats this value as a `JSString`.
-    pub fn to_js_string(&self, context: &JSContext) -> Result<JSString, JSValue> {
+    pub fn to_js_string(&self, context: &JSContext) -> Result<JSString, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let string = unsafe { JSValueToStringCopy(context.inner, self.inner, &mut exception) };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         Ok(JSString::from(string))
     }
 
     // Tries to convert the value to a number
-    pub fn to_number(&self, context: &JSContext) -> Result<f64, JSValue> {
+    pub fn to_number(&self, context: &JSContext) -> Result<f64, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let num = unsafe { JSValueToNumber(context.inner, self.inner, &mut exception) };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         Ok(num)
     }
 
     // Tries to convert the value to an object
-    pub fn to_object(&self, context: &JSContext) -> Result<JSObject, JSValue> {
+    pub fn to_object(&self, context: &JSContext) -> Result<JSObject, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let object_ref = unsafe { JSValueToObject(context.inner, self.inner, &mut exception) };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         let obj = JSObject::from(object_ref);
         Ok(obj)
@@ -218,7 +387,219 @@ impl JSValue {
     }
 }
 
-#[derive(Clone)]
+/// A `JSValue` compared and hashed by identity, to be used as the key of a
+/// `HashMap` or `HashSet`.
+///
+/// Two keys are equal if they hold the same object, which makes this wrapper
+/// suitable to remember the objects already seen while walking an object
+/// graph. Primitive values are compared by their internal representation, so
+/// equal strings or numbers are not guaranteed to be identical: use
+/// `JSValue::strict_eq` to compare them.
+///
+/// Note: the key doesn't protect the value from garbage collection, the
+/// values must be kept alive while they are in the collection.
+///
+/// ```rust
+/// use std::collections::HashSet;
+/// use rusty_jsc::{JSContext, JSValueIdentity};
+///
+/// let mut context = JSContext::default();
+/// let object = context.evaluate_script("const o = {}; o", 1).unwrap();
+/// let same_object = context.evaluate_script("o", 1).unwrap();
+///
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(JSValueIdentity::from(object)));
+/// assert!(!seen.insert(JSValueIdentity::from(same_object)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct JSValueIdentity {
+    value: JSValue,
+}
+
+impl JSValueIdentity {
+    /// Returns the wrapped value.
+    pub fn value(&self) -> &JSValue {
+        &self.value
+    }
+
+    /// Consumes the key and returns the wrapped value.
+    pub fn into_value(self) -> JSValue {
+        self.value
+    }
+}
+
+impl From<JSValue> for JSValueIdentity {
+    fn from(value: JSValue) -> Self {
+        Self { value }
+    }
+}
+
+impl<T> From<&JSObject<T>> for JSValueIdentity {
+    fn from(object: &JSObject<T>) -> Self {
+        Self {
+            value: JSValue::from(object.inner),
+        }
+    }
+}
+
+impl PartialEq for JSValueIdentity {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.value.inner, other.value.inner)
+    }
+}
+
+impl Eq for JSValueIdentity {}
+
+impl std::hash::Hash for JSValueIdentity {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.value.inner, state)
+    }
+}
+
+/// A JavaScript exception, i.e. a value thrown by a script or by the engine.
+///
+/// The thrown value is usually an `Error` object but can be any `JSValue`.
+/// An exception converts back into a `JSValue`, so it can be returned as is
+/// from a callback to re-throw it.
+#[derive(Debug, Clone)]
+pub struct JSException {
+    value: JSValue,
+}
+
+impl JSException {
+    /// Returns the thrown value.
+    pub fn value(&self) -> &JSValue {
+        &self.value
+    }
+
+    /// Consumes the exception and returns the thrown value.
+    pub fn into_value(self) -> JSValue {
+        self.value
+    }
+
+    /// Formats the thrown value as a `JSString`.
+    pub fn to_js_string(&self, context: &JSContext) -> Result<JSString, JSException> {
+        self.value.to_js_string(context)
+    }
+
+    /// Describes the exception for a log or a crash report, with the name of
+    /// the context, the error message and its location, e.g.
+    /// `[tenant-1] TypeError: x is not a function (at script.js:3:12)`.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let exception = context.evaluate_script("throw 'oops'", 1).unwrap_err();
+    /// assert_eq!(exception.describe(&context), "oops");
+    ///
+    /// context.set_name("tenant-1");
+    /// let exception = context
+    ///     .evaluate_script("\n\nthrow new RangeError('too far')", 1)
+    ///     .unwrap_err();
+    /// let description = exception.describe(&context);
+    /// assert!(description.starts_with("[tenant-1] RangeError: too far (at :3:"));
+    /// ```
+    pub fn describe(&self, context: &JSContext) -> String {
+        let mut description = String::new();
+        if let Some(name) = context.name() {
+            description.push_str(&format!("[{name}] "));
+        }
+        match (self.name(context), self.message(context)) {
+            (Some(name), Some(message)) => description.push_str(&format!("{name}: {message}")),
+            _ => description.push_str(
+                &self
+                    .to_js_string(context)
+                    .map(|string| string.to_string_lossy())
+                    .unwrap_or_else(|_| "uncaught exception".to_string()),
+            ),
+        }
+        if let Some(line) = self.line(context) {
+            let url = self.source_url(context).unwrap_or_default();
+            match self.column(context) {
+                Some(column) => description.push_str(&format!(" (at {url}:{line}:{column})")),
+                None => description.push_str(&format!(" (at {url}:{line})")),
+            }
+        }
+        description
+    }
+
+    /// Returns the `name` of a thrown error, e.g. `SyntaxError`.
+    pub fn name(&self, context: &JSContext) -> Option<String> {
+        self.string_property(context, "name")
+    }
+
+    /// Returns the `message` of a thrown error.
+    pub fn message(&self, context: &JSContext) -> Option<String> {
+        self.string_property(context, "message")
+    }
+
+    /// Returns the URL of the source where the error was thrown, if the
+    /// source was given one.
+    pub fn source_url(&self, context: &JSContext) -> Option<String> {
+        self.string_property(context, "sourceURL")
+    }
+
+    /// Returns the line where the error was thrown.
+    pub fn line(&self, context: &JSContext) -> Option<u32> {
+        self.number_property(context, "line")
+    }
+
+    /// Returns the column where the error was thrown.
+    pub fn column(&self, context: &JSContext) -> Option<u32> {
+        self.number_property(context, "column")
+    }
+
+    /// Returns the stack trace of a thrown error.
+    pub fn stack(&self, context: &JSContext) -> Option<String> {
+        self.string_property(context, "stack")
+    }
+
+    fn property(&self, context: &JSContext, name: &str) -> Option<JSValue> {
+        if !self.value.is_object(context) {
+            return None;
+        }
+        let object = self.value.to_object(context).ok()?;
+        let value = object.get_property(context, name).ok()?;
+        if value.is_undefined(context) {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn string_property(&self, context: &JSContext, name: &str) -> Option<String> {
+        let value = self.property(context, name)?;
+        Some(value.to_js_string(context).ok()?.to_string_lossy())
+    }
+
+    fn number_property(&self, context: &JSContext, name: &str) -> Option<u32> {
+        let value = self.property(context, name)?;
+        Some(value.to_number(context).ok()? as u32)
+    }
+}
+
+impl From<JSValue> for JSException {
+    fn from(value: JSValue) -> Self {
+        Self { value }
+    }
+}
+
+impl From<JSValueRef> for JSException {
+    /// Wraps a `JSException` from the `JSValueRef` of a thrown value.
+    fn from(inner: JSValueRef) -> Self {
+        Self {
+            value: JSValue::from(inner),
+        }
+    }
+}
+
+impl From<JSException> for JSValue {
+    fn from(exception: JSException) -> Self {
+        exception.value
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct JSObjectGeneric;
 
 /// A JavaScript object.
@@ -253,7 +634,7 @@ impl<T> JSObject<T> {
         class_name: impl ToString,
         constructor: JSObjectCallAsConstructorCallback,
     ) -> JSObject<JSClass> {
-        let class = JSClass::create_ref(class_name, constructor);
+        let class = JSClass::create_ref(class_name, constructor, JSClassHooks::default());
         unsafe {
             JSObject {
                 inner: JSObjectMake(context.get_ref(), class, std::ptr::null_mut()),
@@ -287,7 +668,7 @@ impl<T> JSObject<T> {
     }
 
     /// Create a new Array Object with the given arguments
-    pub fn new_array(context: &JSContext, args: &[JSValue]) -> Result<Self, JSValue> {
+    pub fn new_array(context: &JSContext, args: &[JSValue]) -> Result<Self, JSException> {
         let args_refs = args.iter().map(|arg| arg.inner).collect::<Vec<_>>();
         let mut exception: JSValueRef = std::ptr::null_mut();
         let o_ref = unsafe {
@@ -299,7 +680,7 @@ impl<T> JSObject<T> {
             )
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         Ok(Self::from(o_ref))
     }
@@ -315,8 +696,71 @@ impl<T> JSObject<T> {
         Self::from(o_ref)
     }
 
+    /// Creates a function from JavaScript source, like the `Function`
+    /// constructor, without building the source of a script.
+    ///
+    /// `source_url` and `starting_line_number` are used in the error
+    /// locations and stack traces. A syntax error in the body is returned as
+    /// the thrown `SyntaxError`, see `JSException::line` and
+    /// `JSException::message` to report it.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, JSObject, JSObjectGeneric};
+    ///
+    /// let context = JSContext::default();
+    /// let sum = JSObject::<JSObjectGeneric>::function_from_source(
+    ///     &context,
+    ///     "sum",
+    ///     &["a", "b"],
+    ///     "return a + b;",
+    ///     Some("formula.js"),
+    ///     1,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(sum.call::<f64>(&context, (), (1, 2)).unwrap(), 3.0);
+    /// ```
+    pub fn function_from_source(
+        context: &JSContext,
+        name: impl Into<JSString>,
+        parameter_names: &[&str],
+        body: &str,
+        source_url: Option<&str>,
+        starting_line_number: i32,
+    ) -> Result<JSObject, JSException> {
+        let name = name.into();
+        let parameter_names = parameter_names
+            .iter()
+            .map(|name| JSString::from_str(name))
+            .collect::<Vec<_>>();
+        let parameter_refs = parameter_names
+            .iter()
+            .map(|name| name.inner)
+            .collect::<Vec<_>>();
+        let body: JSString = body.into();
+        let source_url = source_url.map(JSString::from_str);
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let function = unsafe {
+            JSObjectMakeFunction(
+                context.inner,
+                name.inner,
+                parameter_refs.len() as _,
+                parameter_refs.as_ptr(),
+                body.inner,
+                source_url
+                    .as_ref()
+                    .map_or(std::ptr::null_mut(), |url| url.inner),
+                starting_line_number,
+                &mut exception,
+            )
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(JSObject::from(function))
+    }
+
     /// Calls the object constructor
-    pub fn construct(&self, context: &JSContext, args: &[JSValue]) -> Result<Self, JSValue> {
+    pub fn construct(&self, context: &JSContext, args: &[JSValue]) -> Result<Self, JSException> {
         let args_refs = args.iter().map(|arg| arg.inner).collect::<Vec<_>>();
         let mut exception: JSValueRef = std::ptr::null_mut();
         let result = unsafe {
@@ -329,7 +773,7 @@ impl<T> JSObject<T> {
             )
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         if result.is_null() {
             return Err(JSValue::string(
@@ -338,7 +782,8 @@ impl<T> JSObject<T> {
                     "Can't call constructor for {:?}: not a valid constructor",
                     JSValue::from(self.inner).to_js_string(context)
                 ),
-            ));
+            )
+            .into());
         }
         Ok(Self::from(result))
     }
@@ -349,7 +794,7 @@ impl<T> JSObject<T> {
         context: &JSContext,
         this: Option<&JSObject>,
         args: &[JSValue],
-    ) -> Result<JSValue, JSValue> {
+    ) -> Result<JSValue, JSException> {
         let args_refs = args.iter().map(|arg| arg.inner).collect::<Vec<_>>();
         let mut exception: JSValueRef = std::ptr::null_mut();
         let result = unsafe {
@@ -363,7 +808,7 @@ impl<T> JSObject<T> {
             )
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         if result.is_null() {
             return Err(JSValue::string(
@@ -372,7 +817,8 @@ impl<T> JSObject<T> {
                     "Can't call the object {:?}: not a valid function",
                     JSValue::from(self.inner).to_js_string(context)
                 ),
-            ));
+            )
+            .into());
         }
         Ok(JSValue::from(result))
     }
@@ -380,7 +826,7 @@ impl<T> JSObject<T> {
     pub fn create_typed_array_with_bytes(
         context: &JSContext,
         bytes: &mut [u8],
-    ) -> Result<Self, JSValue> {
+    ) -> Result<Self, JSException> {
         let deallocator_ctx = std::ptr::null_mut();
         let mut exception: JSValueRef = std::ptr::null_mut();
         let result = unsafe {
@@ -395,10 +841,10 @@ impl<T> JSObject<T> {
             )
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         if result.is_null() {
-            return Err(JSValue::string(context, "Can't create a type array"));
+            return Err(JSValue::string(context, "Can't create a type array").into());
         }
         Ok(Self::from(result))
     }
@@ -406,7 +852,7 @@ impl<T> JSObject<T> {
     pub fn create_typed_array_from_buffer(
         context: &JSContext,
         buffer: JSObject,
-    ) -> Result<JSObject, JSValue> {
+    ) -> Result<JSObject, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let result = unsafe {
             JSObjectMakeTypedArrayWithArrayBuffer(
@@ -417,13 +863,14 @@ impl<T> JSObject<T> {
             )
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         if result.is_null() {
             return Err(JSValue::string(
                 context,
                 "Can't create a typed array from the provided buffer",
-            ));
+            )
+            .into());
         }
         Ok(JSObject::from(result))
     }
@@ -435,41 +882,111 @@ impl<T> JSObject<T> {
     /// Only use that buffer in a synchronous context. The pointer (of slice)
     /// returned by this function is temporary and is not guaranteed to remain
     /// valid across JavaScriptCore API calls.
-    pub unsafe fn get_typed_array_buffer(&self, context: &JSContext) -> Result<&mut [u8], JSValue> {
+    pub unsafe fn get_typed_array_buffer(
+        &self,
+        context: &JSContext,
+    ) -> Result<&mut [u8], JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let arr_ptr = JSObjectGetTypedArrayBytesPtr(context.inner, self.inner, &mut exception);
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         let arr_len = JSObjectGetTypedArrayByteLength(context.inner, self.inner, &mut exception);
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
+        }
+        // Empty typed arrays may have no backing store.
+        if arr_ptr.is_null() {
+            return Ok(&mut []);
         }
         let slice = std::slice::from_raw_parts_mut(arr_ptr as _, arr_len as usize);
         Ok(slice)
     }
 
     /// Gets the property of an object.
+    ///
+    /// The property can be named by anything convertible into a
+    /// `PropertyKey`: a string, an index or a symbol. A missing property
+    /// evaluates to `undefined`, as in JavaScript; use `has_property` to tell
+    /// it apart from a property explicitly set to `undefined`. If the
+    /// property is an accessor whose getter throws, the thrown value is
+    /// returned as an error.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let mut object = context
+    ///     .evaluate_script(
+    ///         "({ empty: null, get broken() { throw new TypeError('broken') } })",
+    ///         1,
+    ///     )
+    ///     .unwrap()
+    ///     .to_object(&context)
+    ///     .unwrap();
+    /// assert!(object.get_property(&context, "empty").unwrap().is_null(&context));
+    /// assert!(object.get_property(&context, "missing").unwrap().is_undefined(&context));
+    /// assert!(object.has_property(&context, "empty").unwrap());
+    /// assert!(!object.has_property(&context, "missing").unwrap());
+    /// let exception = object.get_property(&context, "broken").unwrap_err();
+    /// assert_eq!(exception.name(&context).as_deref(), Some("TypeError"));
+    /// assert_eq!(exception.message(&context).as_deref(), Some("broken"));
+    /// assert!(object.delete_property(&context, "empty").unwrap());
+    /// assert!(!object.has_property(&context, "empty").unwrap());
+    /// ```
     pub fn get_property(
         &self,
         context: &JSContext,
-        property_name: impl Into<JSString>,
-    ) -> Option<JSValue> {
-        let property_name = property_name.into();
+        property_key: impl Into<PropertyKey>,
+    ) -> Result<JSValue, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let jsvalue_ref = unsafe {
-            JSObjectGetProperty(
-                context.inner,
-                self.inner,
-                property_name.inner,
-                &mut exception,
-            )
+            match property_key.into() {
+                PropertyKey::String(name) => {
+                    JSObjectGetProperty(context.inner, self.inner, name.inner, &mut exception)
+                }
+                PropertyKey::Index(index) => {
+                    JSObjectGetPropertyAtIndex(context.inner, self.inner, index, &mut exception)
+                }
+                PropertyKey::Symbol(key) | PropertyKey::Value(key) => {
+                    JSObjectGetPropertyForKey(context.inner, self.inner, key.inner, &mut exception)
+                }
+            }
         };
-        if unsafe { JSValueIsNull(context.inner, jsvalue_ref) } {
-            None
-        } else {
-            Some(JSValue::from(jsvalue_ref))
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
+        }
+        Ok(JSValue::from(jsvalue_ref))
+    }
+
+    /// Checks if an object has a property, either as an own property or
+    /// through its prototype chain.
+    pub fn has_property(
+        &self,
+        context: &JSContext,
+        property_key: impl Into<PropertyKey>,
+    ) -> Result<bool, JSException> {
+        let mut exception: JSValueRef = std::ptr::null_mut();
+        let has_property = unsafe {
+            match property_key.into() {
+                PropertyKey::String(name) => {
+                    JSObjectHasProperty(context.inner, self.inner, name.inner)
+                }
+                PropertyKey::Index(index) => JSObjectHasPropertyForKey(
+                    context.inner,
+                    self.inner,
+                    JSValueMakeNumber(context.inner, index as f64),
+                    &mut exception,
+                ),
+                PropertyKey::Symbol(key) | PropertyKey::Value(key) => {
+                    JSObjectHasPropertyForKey(context.inner, self.inner, key.inner, &mut exception)
+                }
+            }
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
         }
+        Ok(has_property)
     }
 
     /// Gets the property of an object at a given index
@@ -477,17 +994,50 @@ impl<T> JSObject<T> {
         &self,
         context: &JSContext,
         property_index: u32,
-    ) -> Result<JSValue, JSValue> {
+    ) -> Result<JSValue, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let property = unsafe {
             JSObjectGetPropertyAtIndex(context.inner, self.inner, property_index, &mut exception)
         };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         Ok(JSValue::from(property))
     }
 
+    /// Gets the prototype of an object, which is `null` at the end of the
+    /// prototype chain.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, JSObject, JSObjectGeneric, JSValue};
+    ///
+    /// let context = JSContext::default();
+    /// let prototype = JSObject::<JSObjectGeneric>::new(&context);
+    /// let mut object = JSObject::<JSObjectGeneric>::new(&context);
+    /// let object_prototype = object.get_prototype(&context);
+    /// assert!(object_prototype.is_object(&context));
+    ///
+    /// object.set_prototype(&context, prototype.clone().into());
+    /// assert!(object
+    ///     .get_prototype(&context)
+    ///     .strict_eq(&context, &prototype.into()));
+    /// object.set_prototype(&context, JSValue::null(&context));
+    /// assert!(object.get_prototype(&context).is_null(&context));
+    /// // Other values are ignored.
+    /// object.set_prototype(&context, JSValue::number(&context, 1.0));
+    /// assert!(object.get_prototype(&context).is_null(&context));
+    /// ```
+    pub fn get_prototype(&self, context: &JSContext) -> JSValue {
+        JSValue::from(unsafe { JSObjectGetPrototype(context.inner, self.inner) })
+    }
+
+    /// Sets the prototype of an object.
+    ///
+    /// The prototype must be an object or `null`, other values are ignored.
+    pub fn set_prototype(&mut self, context: &JSContext, prototype: JSValue) {
+        unsafe { JSObjectSetPrototype(context.inner, self.inner, prototype.inner) }
+    }
+
     pub fn get_property_names(&self, context: &JSContext) -> Vec<String> {
         let property_name_array = unsafe { JSObjectCopyPropertyNames(context.inner, self.inner) };
         let num_properties = unsafe { JSPropertyNameArrayGetCount(property_name_array) };
@@ -502,17 +1052,21 @@ impl<T> JSObject<T> {
     }
 
     // Get the object as an array buffer
-    pub fn get_array_buffer(&self, context: &JSContext) -> Result<&mut [u8], JSValue> {
+    pub fn get_array_buffer(&self, context: &JSContext) -> Result<&mut [u8], JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         let arr_ptr =
             unsafe { JSObjectGetArrayBufferBytesPtr(context.inner, self.inner, &mut exception) };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         let arr_len =
             unsafe { JSObjectGetArrayBufferByteLength(context.inner, self.inner, &mut exception) };
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
+        }
+        // Empty and detached buffers may have no backing store.
+        if arr_ptr.is_null() {
+            return Ok(&mut []);
         }
         let slice = unsafe { std::slice::from_raw_parts_mut(arr_ptr as _, arr_len as usize) };
         Ok(slice)
@@ -522,25 +1076,161 @@ impl<T> JSObject<T> {
     pub fn set_property(
         &mut self,
         context: &JSContext,
-        property_name: impl Into<JSString>,
+        property_key: impl Into<PropertyKey>,
+        value: JSValue,
+    ) -> Result<(), JSException> {
+        self.set_property_with(context, property_key, value, PropertyAttributes::empty())
+    }
+
+    /// Sets the property of an object with the given attributes.
+    ///
+    /// Note: like `JSObjectSetProperty`, the attributes are only applied when
+    /// the property is created. Use `define_property` to change the
+    /// attributes of an existing property.
+    pub fn set_property_with(
+        &mut self,
+        context: &JSContext,
+        property_key: impl Into<PropertyKey>,
         value: JSValue,
-    ) -> Result<(), JSValue> {
-        let property_name = property_name.into();
-        let attributes = 0; // TODO
+        attributes: PropertyAttributes,
+    ) -> Result<(), JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         unsafe {
-            JSObjectSetProperty(
-                context.inner,
-                self.inner,
-                property_name.inner,
-                value.inner,
-                attributes,
-                &mut exception,
-            )
+            match property_key.into() {
+                PropertyKey::String(name) => JSObjectSetProperty(
+                    context.inner,
+                    self.inner,
+                    name.inner,
+                    value.inner,
+                    attributes.bits(),
+                    &mut exception,
+                ),
+                PropertyKey::Index(index) if attributes.is_empty() => JSObjectSetPropertyAtIndex(
+                    context.inner,
+                    self.inner,
+                    index,
+                    value.inner,
+                    &mut exception,
+                ),
+                PropertyKey::Index(index) => JSObjectSetPropertyForKey(
+                    context.inner,
+                    self.inner,
+                    JSValueMakeNumber(context.inner, index as f64),
+                    value.inner,
+                    attributes.bits(),
+                    &mut exception,
+                ),
+                PropertyKey::Symbol(key) | PropertyKey::Value(key) => JSObjectSetPropertyForKey(
+                    context.inner,
+                    self.inner,
+                    key.inner,
+                    value.inner,
+                    attributes.bits(),
+                    &mut exception,
+                ),
+            }
         }
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
+        }
+        Ok(())
+    }
+
+    /// Defines a data property of an object, like `Object.defineProperty`.
+    ///
+    /// Unlike `set_property_with`, this also updates the attributes of an
+    /// existing property, and fails if that property isn't configurable.
+    pub fn define_property(
+        &mut self,
+        context: &JSContext,
+        property_key: impl Into<PropertyKey>,
+        value: JSValue,
+        attributes: PropertyAttributes,
+    ) -> Result<(), JSException> {
+        let mut descriptor = attributes.to_descriptor(context)?;
+        descriptor.set_property(context, "value", value)?;
+        descriptor.set_property(
+            context,
+            "writable",
+            JSValue::boolean(context, !attributes.contains(PropertyAttributes::READ_ONLY)),
+        )?;
+        self.define_own_property(context, property_key.into(), descriptor)
+    }
+
+    /// Defines an accessor property backed by native getter and setter
+    /// functions, like `Object.defineProperty` with `get` and `set`.
+    ///
+    /// Passing `None` as the setter makes the property read-only. The
+    /// `READ_ONLY` attribute has no meaning for accessors and is ignored.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{callback, JSContext, JSObject, JSValue, PropertyAttributes};
+    ///
+    /// #[callback]
+    /// fn version(
+    ///     ctx: JSContext,
+    ///     _function: JSObject,
+    ///     _this: JSObject,
+    ///     _args: &[JSValue],
+    /// ) -> Result<JSValue, JSValue> {
+    ///     Ok(JSValue::string(&ctx, "1.0.0"))
+    /// }
+    ///
+    /// let mut context = JSContext::default();
+    /// let mut global = context.get_global_object();
+    /// global
+    ///     .define_accessor(
+    ///         &context,
+    ///         "version",
+    ///         Some(version),
+    ///         None,
+    ///         PropertyAttributes::DONT_DELETE,
+    ///     )
+    ///     .unwrap();
+    /// let value = context.evaluate_script("delete version; version", 1).unwrap();
+    /// assert_eq!(value.to_js_string(&context).unwrap().to_string(), "1.0.0");
+    /// ```
+    pub fn define_accessor(
+        &mut self,
+        context: &JSContext,
+        property_key: impl Into<PropertyKey>,
+        getter: JSObjectCallAsFunctionCallback,
+        setter: JSObjectCallAsFunctionCallback,
+        attributes: PropertyAttributes,
+    ) -> Result<(), JSException> {
+        let mut descriptor = attributes.to_descriptor(context)?;
+        if getter.is_some() {
+            descriptor.set_property(context, "get", JSValue::callback(context, getter))?;
+        }
+        if setter.is_some() {
+            descriptor.set_property(context, "set", JSValue::callback(context, setter))?;
         }
+        self.define_own_property(context, property_key.into(), descriptor)
+    }
+
+    /// Calls `Object.defineProperty(this, key, descriptor)`.
+    fn define_own_property(
+        &self,
+        context: &JSContext,
+        property_key: PropertyKey,
+        descriptor: JSObject,
+    ) -> Result<(), JSException> {
+        let object = context
+            .get_global_object()
+            .get_property(context, "Object")?
+            .to_object(context)?;
+        let define_property = object
+            .get_property(context, "defineProperty")?
+            .to_object(context)?;
+        define_property.call_as_function(
+            context,
+            Some(&object),
+            &[
+                JSValue::from(self.inner),
+                property_key.into_value(context),
+                descriptor.into(),
+            ],
+        )?;
         Ok(())
     }
 
@@ -550,7 +1240,7 @@ impl<T> JSObject<T> {
         context: &JSContext,
         index: u32,
         value: JSValue,
-    ) -> Result<(), JSValue> {
+    ) -> Result<(), JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
         unsafe {
             JSObjectSetPropertyAtIndex(
@@ -562,23 +1252,212 @@ impl<T> JSObject<T> {
             )
         }
         if !exception.is_null() {
-            return Err(JSValue::from(exception));
+            return Err(JSException::from(exception));
         }
         Ok(())
     }
 
     /// Deletes the property of an object.
-    pub fn delete_property(&mut self, context: &JSContext, property_name: impl ToString) {
-        let property_name = JSString::from_utf8(property_name.to_string()).unwrap();
+    ///
+    /// Returns whether the property was deleted, which is `false` for a
+    /// non-configurable property (see `PropertyAttributes::DONT_DELETE`).
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let value = context.evaluate_script("({ 0: 'a', 1: 'b', name: 'c' })", 1).unwrap();
+    /// let mut object = value.to_object(&context).unwrap();
+    /// let name = String::from("name");
+    /// assert!(object.delete_property(&context, &name).unwrap());
+    /// assert!(object.delete_property(&context, 0).unwrap());
+    /// assert!(object.delete_property(&context, 1usize).unwrap());
+    /// assert!(!object.has_property(&context, 1).unwrap());
+    /// ```
+    pub fn delete_property(
+        &mut self,
+        context: &JSContext,
+        property_key: impl Into<PropertyKey>,
+    ) -> Result<bool, JSException> {
         let mut exception: JSValueRef = std::ptr::null_mut();
-        unsafe {
-            JSObjectDeleteProperty(
-                context.inner,
-                self.inner,
-                property_name.inner,
-                &mut exception,
-            );
+        let deleted = unsafe {
+            match property_key.into() {
+                PropertyKey::String(name) => {
+                    JSObjectDeleteProperty(context.inner, self.inner, name.inner, &mut exception)
+                }
+                PropertyKey::Index(index) => JSObjectDeletePropertyForKey(
+                    context.inner,
+                    self.inner,
+                    JSValueMakeNumber(context.inner, index as f64),
+                    &mut exception,
+                ),
+                PropertyKey::Symbol(key) | PropertyKey::Value(key) => JSObjectDeletePropertyForKey(
+                    context.inner,
+                    self.inner,
+                    key.inner,
+                    &mut exception,
+                ),
+            }
+        };
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
         }
+        Ok(deleted)
+    }
+}
+
+/// The key used to access a property of a `JSObject`.
+///
+/// Most of the time you don't need to build a key yourself: strings, indexes
+/// and `JSValue`s convert into a `PropertyKey` when passed to the property
+/// accessors of `JSObject`.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, PropertyKey};
+///
+/// let mut context = JSContext::default();
+/// let array = context.evaluate_script("[1, 2, 3]", 1).unwrap().to_object(&context).unwrap();
+/// let iterator = PropertyKey::well_known_symbol(&context, "iterator").unwrap();
+/// assert!(array.has_property(&context, iterator).unwrap());
+/// ```
+#[derive(Debug)]
+pub enum PropertyKey {
+    /// A property named by a string, e.g. `object.name`.
+    String(JSString),
+    /// An indexed property, e.g. `array[0]`.
+    Index(u32),
+    /// A property named by a symbol, e.g. `object[Symbol.iterator]`.
+    Symbol(JSValue),
+    /// Any value used as a key, converted with the `ToPropertyKey` semantics
+    /// of the engine.
+    Value(JSValue),
+}
+
+impl PropertyKey {
+    /// Converts this key into the value that JavaScript would use to access
+    /// the property.
+    pub fn into_value(self, context: &JSContext) -> JSValue {
+        match self {
+            PropertyKey::String(name) => JSValue::string(context, name),
+            PropertyKey::Index(index) => JSValue::number(context, index as f64),
+            PropertyKey::Symbol(key) | PropertyKey::Value(key) => key,
+        }
+    }
+
+    /// Creates a key from a new unique symbol with the given description.
+    pub fn symbol(context: &JSContext, description: impl Into<JSString>) -> Self {
+        PropertyKey::Symbol(JSValue::symbol(context, description))
+    }
+
+    /// Creates a key from a well-known symbol such as `iterator`,
+    /// `asyncIterator` or `toStringTag`, i.e. `Symbol[name]`.
+    pub fn well_known_symbol(context: &JSContext, name: &str) -> Result<Self, JSException> {
+        let symbol = JSValue::well_known_symbol(context, name)?;
+        Ok(PropertyKey::Symbol(symbol))
+    }
+}
+
+bitflags::bitflags! {
+    /// The attributes of a property, mapping the `kJSPropertyAttribute*`
+    /// constants of JavaScriptCore.
+    ///
+    /// An empty set describes a writable, enumerable and configurable
+    /// property, like a property created by a plain assignment.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PropertyAttributes: JSPropertyAttributes {
+        /// The property can't be assigned (`writable: false`).
+        const READ_ONLY = kJSPropertyAttributeReadOnly;
+        /// The property isn't listed by `for...in` and `Object.keys`
+        /// (`enumerable: false`).
+        const DONT_ENUM = kJSPropertyAttributeDontEnum;
+        /// The property can't be deleted or redefined (`configurable: false`).
+        const DONT_DELETE = kJSPropertyAttributeDontDelete;
+    }
+}
+
+impl PropertyAttributes {
+    /// Creates a property descriptor object with the `enumerable` and
+    /// `configurable` fields matching these attributes.
+    fn to_descriptor(self, context: &JSContext) -> Result<JSObject, JSException> {
+        let mut descriptor = JSObject::<JSObjectGeneric>::new(context);
+        descriptor.set_property(
+            context,
+            "enumerable",
+            JSValue::boolean(context, !self.contains(PropertyAttributes::DONT_ENUM)),
+        )?;
+        descriptor.set_property(
+            context,
+            "configurable",
+            JSValue::boolean(context, !self.contains(PropertyAttributes::DONT_DELETE)),
+        )?;
+        Ok(descriptor)
+    }
+}
+
+impl From<JSString> for PropertyKey {
+    fn from(name: JSString) -> Self {
+        PropertyKey::String(name)
+    }
+}
+
+impl From<&str> for PropertyKey {
+    fn from(name: &str) -> Self {
+        PropertyKey::String(name.into())
+    }
+}
+
+impl From<String> for PropertyKey {
+    fn from(name: String) -> Self {
+        PropertyKey::String(name.into())
+    }
+}
+
+impl From<&String> for PropertyKey {
+    fn from(name: &String) -> Self {
+        PropertyKey::String(name.as_str().into())
+    }
+}
+
+impl From<std::borrow::Cow<'_, str>> for PropertyKey {
+    fn from(name: std::borrow::Cow<'_, str>) -> Self {
+        PropertyKey::String(name.as_ref().into())
+    }
+}
+
+impl From<char> for PropertyKey {
+    fn from(name: char) -> Self {
+        PropertyKey::String(name.to_string().into())
+    }
+}
+
+impl From<u32> for PropertyKey {
+    fn from(index: u32) -> Self {
+        PropertyKey::Index(index)
+    }
+}
+
+// Other integers are indexes when they fit in a `u32`, and are named by their
+// decimal representation otherwise, like JavaScript does for numeric keys.
+macro_rules! integer_property_keys {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for PropertyKey {
+                fn from(key: $ty) -> Self {
+                    match u32::try_from(key) {
+                        Ok(index) => PropertyKey::Index(index),
+                        Err(_) => PropertyKey::String(key.to_string().into()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_property_keys!(u8, u16, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl From<JSValue> for PropertyKey {
+    fn from(key: JSValue) -> Self {
+        PropertyKey::Value(key)
     }
 }
 
@@ -596,6 +1475,47 @@ impl From<JSObject> for JSObjectRef {
 
 // Private data implementation. This is available only for JSObject<JSClass> and
 // JSObject<JSObjectGenericClass>.
+//
+// The objects of the classes created with `JSClass::create` get a
+// `PrivateData` when they are initialized, which is freed by the finalizer of
+// the classes. It holds the data as a `Box<dyn Any>`, which records its type
+// so it can be downcasted safely, behind a header telling it apart from the
+// private pointers of the objects of other classes, which the crate doesn't
+// own. Any number of `JSObject` handles can point to the same object, so the
+// data is borrowed through the `RefCell` like shared state.
+
+/// The first word of a `PrivateData`.
+const PRIVATE_DATA_MAGIC: u64 = u64::from_be_bytes(*b"rustyjsc");
+
+#[repr(C)]
+struct PrivateData {
+    magic: u64,
+    data: RefCell<Option<Box<dyn Any>>>,
+    /// The panic policy of the context the object was created in, for the
+    /// panics of the destructor of the data.
+    policy: Rc<Cell<PanicPolicy>>,
+}
+
+/// Returns the private data of an object of a class created with
+/// `JSClass::create`, or `None` for the other objects.
+fn private_data_of<'a>(object: JSObjectRef) -> Option<&'a PrivateData> {
+    let data = unsafe { JSObjectGetPrivate(object) } as *const PrivateData;
+    if data.is_null() || !data.is_aligned() {
+        return None;
+    }
+    // The private pointer of another class can point to anything, so only
+    // the header is read before the pointer is trusted.
+    let magic = unsafe { std::ptr::read(data as *const u64) };
+    (magic == PRIVATE_DATA_MAGIC).then(|| unsafe { &*data })
+}
+
+/// Checks if an object is an instance of a class, created by the crate or
+/// not, rather than a regular object.
+pub(crate) fn is_class_object(object: JSObjectRef) -> bool {
+    // Setting the private data to its current value only succeeds for the
+    // objects of a class.
+    unsafe { JSObjectSetPrivate(object, JSObjectGetPrivate(object)) }
+}
 
 pub trait HasPrivateData {}
 impl HasPrivateData for JSObject<JSObjectGenericClass> {}
@@ -606,27 +1526,141 @@ where
     JSObject<T>: HasPrivateData,
 {
     /// Set private data
-    pub fn set_private_data<N>(&mut self, data: N) -> Result<(), Box<N>> {
-        let boxed = Box::new(data);
-        let data_ptr = Box::into_raw(boxed);
-        if !unsafe { JSObjectSetPrivate(self.inner, data_ptr as _) } {
-            return Err(unsafe { Box::from_raw(data_ptr) });
-        }
+    ///
+    /// Any data previously stored in the object is dropped. The data is
+    /// dropped as well when the object is garbage collected.
+    ///
+    /// Fails, returning the data, if the object isn't of a class created with
+    /// `JSClass::create` or if its current data is borrowed.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSClass, JSContext};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// #[derive(Debug)]
+    /// struct Counted(Rc<Cell<usize>>);
+    ///
+    /// impl Drop for Counted {
+    ///     fn drop(&mut self) {
+    ///         self.0.set(self.0.get() + 1);
+    ///     }
+    /// }
+    ///
+    /// let mut context = JSContext::default();
+    /// let class = JSClass::create("Counted", None);
+    /// let dropped = Rc::new(Cell::new(0));
+    ///
+    /// let mut object = class.make_object(&context);
+    /// object.set_private_data(Counted(dropped.clone())).unwrap();
+    /// // Replacing the data drops the previous one.
+    /// object.set_private_data(Counted(dropped.clone())).unwrap();
+    /// assert_eq!(dropped.get(), 1);
+    /// assert!(object.private_data::<String>().is_none());
+    /// let taken = object.take_private_data::<Counted>().unwrap();
+    /// assert!(object.private_data::<Counted>().is_none());
+    /// drop(taken);
+    /// assert_eq!(dropped.get(), 2);
+    ///
+    /// // The data is dropped when the object is garbage collected, which
+    /// // isn't deterministic, so garbage is created until it happens.
+    /// for _ in 0..1000 {
+    ///     let mut object = class.make_object(&context);
+    ///     object.set_private_data(Counted(dropped.clone())).unwrap();
+    /// }
+    /// for _ in 0..100 {
+    ///     context
+    ///         .evaluate_script("for (let i = 0; i < 100000; i++) ({})", 1)
+    ///         .unwrap();
+    ///     context.virtual_machine().garbage_collect();
+    ///     if dropped.get() > 2 {
+    ///         break;
+    ///     }
+    /// }
+    /// assert!(dropped.get() > 2);
+    /// ```
+    pub fn set_private_data<N: Any>(&mut self, data: N) -> Result<(), Box<N>> {
+        let Some(private_data) = private_data_of(self.inner) else {
+            return Err(Box::new(data));
+        };
+        let Ok(mut current) = private_data.data.try_borrow_mut() else {
+            return Err(Box::new(data));
+        };
+        let previous = current.replace(Box::new(data));
+        // The previous data is dropped once the cell is released, since its
+        // destructor can access the object.
+        drop(current);
+        drop(previous);
         Ok(())
     }
 
     /// Get private data
     ///
-    /// # Safety
-    /// The pointer to the private data isn't guaranted to be type N if you put
-    /// something else before.
-    pub unsafe fn get_private_data<N>(&mut self) -> Option<*mut N> {
-        let data = JSObjectGetPrivate(self.inner);
-        if data.is_null() {
-            None
-        } else {
-            Some(data as _)
+    /// Returns `None` if the object has no private data or if the data isn't
+    /// of type `N`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is mutably borrowed, e.g. through another handle
+    /// to the same object.
+    pub fn private_data<N: Any>(&self) -> Option<Ref<'_, N>> {
+        let data = private_data_of(self.inner)?.data.borrow();
+        Ref::filter_map(data, |data| data.as_ref()?.downcast_ref::<N>()).ok()
+    }
+
+    /// Get private data as a mutable reference
+    ///
+    /// Returns `None` if the object has no private data or if the data isn't
+    /// of type `N`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is already borrowed, e.g. through another handle
+    /// to the same object.
+    pub fn private_data_mut<N: Any>(&mut self) -> Option<RefMut<'_, N>> {
+        let data = private_data_of(self.inner)?.data.borrow_mut();
+        RefMut::filter_map(data, |data| data.as_mut()?.downcast_mut::<N>()).ok()
+    }
+
+    /// Remove the private data from the object and return it
+    ///
+    /// Returns `None`, leaving the data in place, if the object has no
+    /// private data, if the data isn't of type `N` or if it's borrowed.
+    pub fn take_private_data<N: Any>(&mut self) -> Option<N> {
+        let mut data = private_data_of(self.inner)?.data.try_borrow_mut().ok()?;
+        if !data.as_ref()?.is::<N>() {
+            return None;
         }
+        data.take()?.downcast::<N>().ok().map(|data| *data)
+    }
+}
+
+/// Initializer of the classes created with `JSClass::create`, which
+/// allocates the private data of the new object.
+unsafe extern "C" fn initialize_private_data(ctx: JSContextRef, object: JSObjectRef) {
+    if JSObjectGetPrivate(object).is_null() {
+        let data = Box::new(PrivateData {
+            magic: PRIVATE_DATA_MAGIC,
+            data: RefCell::new(None),
+            policy: crate::panic::policy_cell(ctx),
+        });
+        JSObjectSetPrivate(object, Box::into_raw(data) as _);
+    }
+}
+
+/// Finalizer of the classes created with `JSClass::create`, which drops the
+/// private data of the collected object.
+unsafe extern "C" fn finalize_private_data(object: JSObjectRef) {
+    let Some(data) = private_data_of(object) else {
+        return;
+    };
+    JSObjectSetPrivate(object, std::ptr::null_mut());
+    let PrivateData { data, policy, .. } =
+        *Box::from_raw(data as *const PrivateData as *mut PrivateData);
+    // There is no context to throw into during garbage collection, so a
+    // panic in the destructor of the data can only be reported.
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(|| drop(data))) {
+        crate::panic::handle_finalizer_panic(payload, policy.get());
     }
 }
 
@@ -634,30 +1668,86 @@ pub struct JSClass {
     inner: JSClassRef,
 }
 
+/// Native callbacks intercepting the property accesses on the objects of a
+/// class, see `JSClass::create_with_hooks`.
+///
+/// The callbacks follow the JavaScriptCore conventions: a `get_property` or
+/// `set_property` hook returning null or `false` lets the access continue to
+/// the regular properties and the prototype chain. Write them with the
+/// `class_hook` macro, which catches the panics of the hook.
+///
+/// ```rust
+/// use rusty_jsc::{class_hook, JSClass, JSClassHooks, JSContext, JSObject, JSString, JSValue};
+///
+/// #[class_hook(get_property)]
+/// fn answer(
+///     context: JSContext,
+///     _object: JSObject,
+///     name: JSString,
+/// ) -> Result<Option<JSValue>, JSValue> {
+///     Ok((name == "answer").then(|| JSValue::number(&context, 42.0)))
+/// }
+///
+/// let hooks = JSClassHooks {
+///     get_property: Some(answer),
+///     ..Default::default()
+/// };
+/// let class = JSClass::create_with_hooks("Oracle", None, hooks);
+/// let mut context = JSContext::builder().global_class(class).build();
+/// let value = context.evaluate_script("answer + 1", 1).unwrap();
+/// assert_eq!(value.to_number(&context).unwrap(), 43.0);
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JSClassHooks {
+    pub has_property: JSObjectHasPropertyCallback,
+    pub get_property: JSObjectGetPropertyCallback,
+    pub set_property: JSObjectSetPropertyCallback,
+    pub delete_property: JSObjectDeletePropertyCallback,
+    pub get_property_names: JSObjectGetPropertyNamesCallback,
+}
+
 /// Specification of a `JSObject` as `JSObject<JSObjectGenericClass>` that is a
 /// variation of a `JSGenericObject` available to store private data.
 pub struct JSObjectGenericClass;
 
 impl JSClass {
     pub fn create(name: impl ToString, constructor: JSObjectCallAsConstructorCallback) -> JSClass {
-        JSClass::create_ref(name, constructor).into()
+        JSClass::create_ref(name, constructor, JSClassHooks::default()).into()
+    }
+
+    /// Creates a class whose objects call the given native hooks when their
+    /// properties are accessed.
+    pub fn create_with_hooks(
+        name: impl ToString,
+        constructor: JSObjectCallAsConstructorCallback,
+        hooks: JSClassHooks,
+    ) -> JSClass {
+        JSClass::create_ref(name, constructor, hooks).into()
     }
 
     fn create_ref(
         name: impl ToString,
         constructor: JSObjectCallAsConstructorCallback,
+        hooks: JSClassHooks,
     ) -> JSClassRef {
+        // The name must outlive `JSClassCreate`, which copies it.
+        let name = CString::new(name.to_string()).unwrap();
         let mut class_definition = unsafe { kJSClassDefinitionEmpty };
-        class_definition.className = name.to_string().as_bytes().as_ptr() as _;
+        class_definition.className = name.as_ptr();
         class_definition.callAsConstructor = constructor;
+        class_definition.initialize = Some(initialize_private_data);
+        class_definition.finalize = Some(finalize_private_data);
+        class_definition.hasProperty = hooks.has_property;
+        class_definition.getProperty = hooks.get_property;
+        class_definition.setProperty = hooks.set_property;
+        class_definition.deleteProperty = hooks.delete_property;
+        class_definition.getPropertyNames = hooks.get_property_names;
         // TODO: we should manage the attributes and static parameters (even if it
         //       looks broken for the version 4.0)
         // class_definition.attributes = kJSClassAttributeNoAutomaticPrototype;
         // class_definition.staticValues = values;
         // class_definition.staticFunctions = log;
 
-        // TODO: manage private datas if any, we give std::ptr::null_mut() for the
-        //       moment.
         unsafe {
             let class = JSClassCreate([class_definition].as_ptr() as _);
             JSClassRetain(class);
@@ -665,6 +1755,26 @@ impl JSClass {
         }
     }
 
+    /// Creates an instance of this class for a constructor call, taking the
+    /// prototype from the `prototype` property of `constructor`, the object
+    /// `new` was applied to.
+    ///
+    /// JavaScriptCore passes the callee rather than `new.target` to
+    /// constructor callbacks, so instances of a JavaScript subclass still get
+    /// the prototype of this constructor.
+    pub fn make_instance<T>(
+        &self,
+        context: &JSContext,
+        constructor: &JSObject<T>,
+    ) -> Result<JSObject<JSObjectGenericClass>, JSException> {
+        let mut instance = self.make_object(context);
+        let prototype = constructor.get_property(context, "prototype")?;
+        if prototype.is_object(context) {
+            instance.set_prototype(context, prototype);
+        }
+        Ok(instance)
+    }
+
     /// Creates a generic object derived from this class.
     ///
     /// Note: if you drop this object that won't affect the class itself.
@@ -810,68 +1920,109 @@ impl Drop for JSProtected {
 /// contexts)
 unsafe impl Send for JSObject<JSProtected> {}
 
-/// A JavaScript virtual machine.
-#[derive(Clone)]
-pub struct JSVirtualMachine {
-    context_group: JSContextGroupRef,
+/// A JavaScript execution context.
+///
+/// Every `JSContext` and `Persistent` handle retains its global context. The
+/// state kept for the context, such as its console, its panic policy and its
+/// interned strings, is shared by the handles and released with the last of
+/// them. Dropping the context created by `new`, `builder` or a virtual
+/// machine cancels its pending tasks.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, PanicPolicy};
+///
+/// let context = JSContext::default();
+/// context.set_panic_policy(PanicPolicy::Log);
+/// let copy = JSContext::from(context.get_ref());
+/// drop(context);
+/// assert_eq!(copy.panic_policy(), PanicPolicy::Log);
+/// ```
+pub struct JSContext {
+    inner: JSContextRef,
     global_context: JSGlobalContextRef,
+    /// Whether the global context was created by this object (rather than
+    /// wrapped by `from`), in which case its pending tasks are cancelled on
+    /// drop.
+    owns_context: bool,
+    /// The virtual machine of the context, looked up on first use for the
+    /// wrapped contexts.
+    vm: OnceCell<JSVirtualMachine>,
 }
 
-impl JSVirtualMachine {
-    /// Creates a new `JSVirtualMachine` object from a context.
-    fn from(context: JSContextRef) -> Self {
-        let global_context = unsafe { JSContextGetGlobalContext(context) };
-        unsafe {
-            JSGlobalContextRetain(global_context);
-        }
-        let context_group = unsafe { JSContextGetGroup(global_context) };
-        unsafe {
-            JSContextGroupRetain(context_group);
-        }
-        Self {
-            context_group,
-            global_context,
-        }
+impl JSContext {
+    /// Create a new context in the same virtual machine
+    pub fn split(&self) -> Self {
+        self.virtual_machine().create_context()
     }
 
-    /// Creates a new `JSVirtualMachine` object.
-    fn new() -> Self {
-        let context_group = unsafe { JSContextGroupCreate() };
-        let global_context =
-            unsafe { JSGlobalContextCreateInGroup(context_group, std::ptr::null_mut()) };
-        Self {
-            context_group,
+    /// Creates a new context in `vm`, whose global object is of the given
+    /// class, or a regular object if `global_class` is null.
+    fn in_virtual_machine(vm: &JSVirtualMachine, global_class: JSClassRef) -> Self {
+        let global_context = unsafe { JSGlobalContextCreateInGroup(vm.group(), global_class) };
+        count_handle(global_context);
+        vm.add_context(global_context);
+        let context = Self {
+            inner: global_context,
             global_context,
-        }
+            owns_context: true,
+            vm: OnceCell::from(vm.clone()),
+        };
+        // The built-ins are captured before any script can replace them.
+        let _ = builtins::builtins(&context);
+        context
     }
-}
 
-impl Drop for JSVirtualMachine {
-    fn drop(&mut self) {
-        unsafe {
-            JSGlobalContextRelease(self.global_context);
-            JSContextGroupRelease(self.context_group);
-        }
+    /// Sets the name of this context, shown in its `Debug` output, in the
+    /// messages logged for it and in the Web Inspector.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let context = JSContext::default();
+    /// assert_eq!(context.name(), None);
+    /// assert!(format!("{context:?}").contains("name: None"));
+    /// context.set_name("tenant-1");
+    /// assert_eq!(context.name().as_deref(), Some("tenant-1"));
+    /// assert!(format!("{context:?}").contains(r#"name: Some("tenant-1")"#));
+    /// ```
+    pub fn set_name(&self, name: &str) {
+        let name: JSString = name.into();
+        unsafe { JSGlobalContextSetName(self.global_context, name.inner) }
     }
-}
 
-/// A JavaScript execution context.
-pub struct JSContext {
-    inner: JSContextRef,
-    vm: JSVirtualMachine,
-}
+    /// Returns the name of this context.
+    pub fn name(&self) -> Option<String> {
+        context_name(self.inner)
+    }
 
-impl JSContext {
-    /// Create a new context in the same virtual machine
-    pub fn split(&self) -> Self {
-        unsafe {
-            let context = JSGlobalContextCreateInGroup(self.vm.context_group, std::ptr::null_mut());
-            JSContextGroupRetain(self.vm.context_group);
-            JSGlobalContextRetain(context);
-            let mut vm = self.vm.clone();
-            vm.global_context = context;
-            Self { inner: context, vm }
-        }
+    /// Returns the virtual machine of this context.
+    pub fn virtual_machine(&self) -> &JSVirtualMachine {
+        self.vm.get_or_init(|| {
+            JSVirtualMachine::from_group(unsafe { JSContextGetGroup(self.global_context) })
+        })
+    }
+
+    /// Returns a builder to configure a new context.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSClass, JSContext};
+    ///
+    /// let mut context = JSContext::builder()
+    ///     .global_class(JSClass::create("Sandbox", None))
+    ///     .build();
+    /// let mut global = context.global_object_of_class().unwrap();
+    /// global.set_private_data(String::from("tenant-1")).unwrap();
+    /// ```
+    pub fn builder() -> JSContextBuilder {
+        JSContextBuilder::default()
+    }
+
+    /// Returns the global object of a context created with a global class,
+    /// which can hold private data, or `None` if the global object is a
+    /// regular object.
+    pub fn global_object_of_class(&self) -> Option<JSObject<JSObjectGenericClass>> {
+        let global = unsafe { JSContextGetGlobalObject(self.inner) };
+        is_class_object(global).then(|| JSObject::from(global))
     }
 
     /// Get inner opaque object.
@@ -885,19 +2036,92 @@ impl JSContext {
     /// If you want to create a `JSContext` object within an existing virtual
     /// machine, please use the `with_virtual_machine` associated function.
     pub fn new() -> Self {
-        let vm = JSVirtualMachine::new();
-        Self {
-            inner: vm.global_context,
-            vm,
-        }
+        JSVirtualMachine::new().create_context()
     }
 
     /// Create a new `JSContext` object within the provided `JSVirtualMachine`.
     pub fn with_virtual_machine(vm: JSVirtualMachine) -> Self {
-        Self {
-            inner: vm.global_context,
-            vm,
-        }
+        vm.create_context()
+    }
+
+    /// Returns a `JSString` for `value` from the interning cache of this
+    /// context, creating it on the first use.
+    ///
+    /// Interning avoids converting the same property names over and over in
+    /// hot code paths. The cache lives as long as the context, so only intern
+    /// a bounded set of strings.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let context = JSContext::default();
+    /// let global = context.get_global_object();
+    /// let name = context.intern("globalThis");
+    /// assert!(global.has_property(&context, name).unwrap());
+    /// ```
+    pub fn intern(&self, value: &str) -> JSString {
+        internal::intern(self.global_context, value)
+    }
+
+    /// Sets what happens when Rust code called from JavaScript in this
+    /// context panics. The default is `PanicPolicy::Throw`.
+    ///
+    /// The policy is shared with the contexts wrapping the same global
+    /// context, such as the ones passed to callbacks. Class hooks that can't
+    /// throw report the panic under `PanicPolicy::Throw`. Private data is
+    /// dropped during garbage collection, outside of any context, so a panic
+    /// in its destructor aborts if the context the object was created in has
+    /// the `Abort` policy and is reported otherwise.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        crate::panic::set_panic_policy(self.global_context, policy);
+    }
+
+    /// Returns the panic policy of this context.
+    pub fn panic_policy(&self) -> PanicPolicy {
+        crate::panic::panic_policy(self.inner)
+    }
+
+    /// Spawns a future on the executor of this context.
+    ///
+    /// The executor runs on the thread of the context: the future is only
+    /// polled by `run_pending_tasks` and `run_tasks`, so it doesn't need to be
+    /// `Send`. The garbage collector doesn't see the values held by the
+    /// future though: keep JavaScript values across awaits in `Persistent`
+    /// handles.
+    pub fn spawn_local(&self, future: impl std::future::Future<Output = ()> + 'static) {
+        executor::spawn_local(self, future);
+    }
+
+    /// Polls the spawned futures that are ready to make progress, without
+    /// blocking, and returns the number of futures that are not complete.
+    pub fn run_pending_tasks(&self) -> usize {
+        executor::run_pending_tasks(self)
+    }
+
+    /// Runs the spawned futures until they are all complete, blocking the
+    /// thread while they wait.
+    pub fn run_tasks(&self) {
+        executor::run_tasks(self);
+    }
+
+    /// Installs a global `console` object writing to `sink`.
+    ///
+    /// The console supports `log`, `info`, `debug`, `warn`, `error`,
+    /// `trace`, `dir`, `assert`, `count`, `countReset`, `time`, `timeLog`,
+    /// `timeEnd`, `group`, `groupCollapsed`, `groupEnd` and `table`, and
+    /// formats its arguments like `util.format` in Node.js.
+    ///
+    /// ```rust
+    /// use rusty_jsc::{JSContext, StdioSink};
+    ///
+    /// let mut context = JSContext::default();
+    /// context.install_console(StdioSink).unwrap();
+    /// context
+    ///     .evaluate_script("console.log('%s is %d', 'answer', 42, { nested: [1, 2] })", 1)
+    ///     .unwrap();
+    /// ```
+    pub fn install_console(&self, sink: impl ConsoleSink + 'static) -> Result<(), JSException> {
+        console::install(self, sink)
     }
 
     /// Returns the context global object.
@@ -907,21 +2131,30 @@ impl JSContext {
 
     /// Evaluate the script.
     ///
-    /// Returns the value the script evaluates to. If the script throws an
-    /// exception, this function returns `None`. You can query the thrown
-    /// exception with the `get_exception` method.
+    /// Returns the value the script evaluates to, or the exception if the
+    /// script throws.
+    ///
+    /// ```rust
+    /// use rusty_jsc::JSContext;
+    ///
+    /// let mut context = JSContext::default();
+    /// let value = context.evaluate_script("6 * 7", 1).unwrap();
+    /// assert_eq!(value.to_number(&context).unwrap(), 42.0);
+    /// let exception = context.evaluate_script("null.x", 1).unwrap_err();
+    /// assert_eq!(exception.name(&context).as_deref(), Some("TypeError"));
+    /// ```
     pub fn evaluate_script(
         &mut self,
         script: &str,
         starting_line_number: i32,
-    ) -> Result<JSValue, JSValue> {
+    ) -> Result<JSValue, JSException> {
         let script: JSString = script.into();
         let this_object = std::ptr::null_mut();
         let source_url = std::ptr::null_mut();
         let mut exception: JSValueRef = std::ptr::null_mut();
         let value = unsafe {
             JSEvaluateScript(
-                self.vm.global_context,
+                self.global_context,
                 script.inner,
                 this_object,
                 source_url,
@@ -929,21 +2162,69 @@ impl JSContext {
                 &mut exception,
             )
         };
-        let value = JSValue::from(value);
-        if value.is_null(self) {
-            Err(JSValue::from(exception))
-        } else {
-            Ok(value)
+        if !exception.is_null() {
+            return Err(JSException::from(exception));
         }
+        Ok(JSValue::from(value))
+    }
+}
+
+/// A builder to configure a new `JSContext`, created by `JSContext::builder`.
+#[derive(Default)]
+pub struct JSContextBuilder {
+    global_class: Option<JSClass>,
+    vm: Option<JSVirtualMachine>,
+}
+
+impl JSContextBuilder {
+    /// Sets the class of the global object.
+    ///
+    /// The hooks of the class apply to the global object, e.g. to create
+    /// globals lazily or to handle the lookups of unknown identifiers, and
+    /// the global object can hold private data, see
+    /// `JSContext::global_object_of_class`.
+    pub fn global_class(mut self, class: JSClass) -> Self {
+        self.global_class = Some(class);
+        self
+    }
+
+    /// Sets the virtual machine of the context. By default, the context is
+    /// created in a new virtual machine.
+    pub fn virtual_machine(mut self, vm: &JSVirtualMachine) -> Self {
+        self.vm = Some(vm.clone());
+        self
+    }
+
+    /// Creates the context.
+    pub fn build(self) -> JSContext {
+        let global_class = self
+            .global_class
+            .as_ref()
+            .map_or(std::ptr::null_mut(), |class| class.inner);
+        let vm = self.vm.unwrap_or_default();
+        JSContext::in_virtual_machine(&vm, global_class)
     }
 }
 
 impl fmt::Debug for JSContext {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("JSContext").finish()
+        f.debug_struct("JSContext")
+            .field("name", &self.name())
+            .field("global_context", &self.global_context)
+            .field("vm", self.virtual_machine())
+            .finish()
     }
 }
 
+/// Returns the name of the global context of `context`, if it has one.
+pub(crate) fn context_name(context: JSContextRef) -> Option<String> {
+    let name = unsafe { JSGlobalContextCopyName(JSContextGetGlobalContext(context)) };
+    if name.is_null() {
+        return None;
+    }
+    Some(JSString::from(name).to_string_lossy())
+}
+
 impl Default for JSContext {
     fn default() -> Self {
         JSContext::new()
@@ -952,7 +2233,82 @@ impl Default for JSContext {
 
 impl From<JSContextRef> for JSContext {
     fn from(ctx: JSContextRef) -> Self {
-        let vm = JSVirtualMachine::from(ctx);
-        Self { inner: ctx, vm }
+        Self::wrap(ctx)
+    }
+}
+
+impl JSContext {
+    /// Wraps a context, retaining its global context.
+    fn wrap(ctx: JSContextRef) -> Self {
+        let global_context = unsafe { JSContextGetGlobalContext(ctx) };
+        retain_global_context(global_context);
+        Self {
+            inner: ctx,
+            global_context,
+            owns_context: false,
+            vm: OnceCell::new(),
+        }
+    }
+}
+
+impl Drop for JSContext {
+    fn drop(&mut self) {
+        // The pending tasks usually hold handles to the context, so they are
+        // cancelled with the context that created it rather than with the
+        // last handle.
+        if self.owns_context {
+            executor::forget_tasks(self.global_context);
+        }
+        release_global_context(self.global_context);
     }
 }
+
+thread_local! {
+    /// The number of `JSContext` and `Persistent` handles to each global
+    /// context, keyed by its address.
+    static CONTEXT_HANDLES: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+}
+
+/// Retains a global context for a new handle.
+pub(crate) fn retain_global_context(context: JSGlobalContextRef) {
+    unsafe { JSGlobalContextRetain(context) };
+    count_handle(context);
+}
+
+fn count_handle(context: JSGlobalContextRef) {
+    let _ = CONTEXT_HANDLES
+        .try_with(|handles| *handles.borrow_mut().entry(context as usize).or_default() += 1);
+}
+
+/// Releases a global context for a dropped handle, forgetting the state kept
+/// for the context along with its last handle.
+pub(crate) fn release_global_context(context: JSGlobalContextRef) {
+    // The table may already be destroyed if the handle is dropped while the
+    // thread exits, and then there is no state left to forget.
+    let last = CONTEXT_HANDLES
+        .try_with(|handles| {
+            let mut handles = handles.borrow_mut();
+            let count = handles.get_mut(&(context as usize))?;
+            *count -= 1;
+            if *count > 0 {
+                return Some(false);
+            }
+            handles.remove(&(context as usize));
+            Some(true)
+        })
+        .ok()
+        .flatten()
+        .unwrap_or(false);
+    // The state is forgotten while the context is still retained, since it
+    // holds protected values, and without borrowing the table, since it
+    // holds handles to other contexts.
+    if last {
+        internal::forget_interned(context);
+        builtins::forget_builtins(context);
+        crate::panic::forget_panic_policy(context);
+        executor::forget_tasks(context);
+        console::forget_console(context);
+        vm::forget_context(context);
+    }
+    unsafe { JSGlobalContextRelease(context) };
+}
@@ -0,0 +1,269 @@
+//! Panic handling at the boundary between JavaScriptCore and Rust.
+//!
+//! A panic must not unwind through the frames of JavaScriptCore, so the
+//! functions generated by the `callback`, `constructor` and `class_hook`
+//! macros catch the panics of the user code and handle them according to the
+//! `PanicPolicy` of the context.
+
+use rusty_jsc_sys::*;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::RwLock;
+
+use crate::JSString;
+
+/// What to do when Rust code called from JavaScript panics, see
+/// `JSContext::set_panic_policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Throw a JavaScript `Error` with the panic message.
+    #[default]
+    Throw,
+    /// Report the panic message, see `set_panic_logger`, and return
+    /// `undefined`.
+    ///
+    /// Constructors can't return `undefined` and async callbacks have to
+    /// settle their promise, so they still throw.
+    Log,
+    /// Abort the process.
+    Abort,
+}
+
+thread_local! {
+    /// The panic policies of the global contexts, keyed by their address.
+    ///
+    /// The private data of the objects of a context shares its policy, since
+    /// it can be dropped after the context.
+    static PANIC_POLICIES: RefCell<HashMap<usize, Rc<Cell<PanicPolicy>>>> =
+        RefCell::new(HashMap::new());
+}
+
+type PanicLogger = Box<dyn Fn(&str) + Send + Sync>;
+
+/// The function reporting the panics that aren't thrown, see
+/// `set_panic_logger`.
+static PANIC_LOGGER: RwLock<Option<PanicLogger>> = RwLock::new(None);
+
+/// Sets the function reporting the panics that can't be thrown into
+/// JavaScript, e.g. under `PanicPolicy::Log` or before aborting, and the
+/// other errors nothing can be thrown into, e.g. a promise of an async
+/// callback that can't be settled.
+///
+/// Without a logger, they are reported to the `log` crate, with the
+/// `rusty_jsc` target, when the `log` feature is enabled, and are otherwise
+/// not reported.
+///
+/// ```rust
+/// rusty_jsc::set_panic_logger(|message| eprintln!("{message}"));
+/// ```
+pub fn set_panic_logger(logger: impl Fn(&str) + Send + Sync + 'static) {
+    let mut panic_logger = PANIC_LOGGER
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    *panic_logger = Some(Box::new(logger));
+}
+
+pub(crate) fn report(message: &str) {
+    let panic_logger = PANIC_LOGGER
+        .read()
+        .unwrap_or_else(|error| error.into_inner());
+    match &*panic_logger {
+        Some(logger) => logger(message),
+        #[cfg(feature = "log")]
+        None => log::error!(target: "rusty_jsc", "{message}"),
+        #[cfg(not(feature = "log"))]
+        None => {}
+    }
+}
+
+pub(crate) fn set_panic_policy(context: JSGlobalContextRef, policy: PanicPolicy) {
+    policy_cell(context).set(policy);
+}
+
+/// Returns the policy of a context, shared with the private data of its
+/// objects.
+pub(crate) fn policy_cell(context: JSContextRef) -> Rc<Cell<PanicPolicy>> {
+    let context = unsafe { JSContextGetGlobalContext(context) };
+    // The table may already be destroyed if an object is created while the
+    // thread exits.
+    PANIC_POLICIES
+        .try_with(|policies| {
+            policies
+                .borrow_mut()
+                .entry(context as usize)
+                .or_default()
+                .clone()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn panic_policy(context: JSContextRef) -> PanicPolicy {
+    let context = unsafe { JSContextGetGlobalContext(context) };
+    PANIC_POLICIES
+        .try_with(|policies| {
+            policies
+                .borrow()
+                .get(&(context as usize))
+                .map(|policy| policy.get())
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Releases the panic policy of the given context.
+pub(crate) fn forget_panic_policy(context: JSGlobalContextRef) {
+    // The table may already be destroyed if the context is dropped while the
+    // thread exits.
+    let _ = PANIC_POLICIES.try_with(|policies| policies.borrow_mut().remove(&(context as usize)));
+}
+
+/// Extracts the message of a panic payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Rust code panicked".to_string()
+    }
+}
+
+/// Handles a panic caught in a function callback, setting `exception` unless
+/// the policy is to log it.
+///
+/// # Safety
+///
+/// `context` must be a valid context and `exception` a valid pointer.
+#[doc(hidden)]
+pub unsafe fn handle_callback_panic(
+    context: JSContextRef,
+    payload: Box<dyn Any + Send>,
+    exception: *mut JSValueRef,
+) {
+    let message = panic_message(&*payload);
+    match panic_policy(context) {
+        PanicPolicy::Throw => *exception = make_error(context, &message),
+        PanicPolicy::Log => report(&format!(
+            "callback panicked{}: {message}",
+            in_context(context)
+        )),
+        PanicPolicy::Abort => abort(context, &message),
+    }
+}
+
+/// Returns `undefined` from a native function, throwing the error or the
+/// panic of its body.
+pub(crate) unsafe fn settle_callback(
+    ctx: JSContextRef,
+    result: std::thread::Result<Result<(), crate::JSException>>,
+    exception: *mut JSValueRef,
+) -> JSValueRef {
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(error)) => *exception = error.value().get_ref(),
+        Err(payload) => handle_callback_panic(ctx, payload, exception),
+    }
+    JSValueMakeUndefined(ctx)
+}
+
+/// Handles a panic caught in a constructor callback, setting `exception`.
+///
+/// # Safety
+///
+/// `context` must be a valid context and `exception` a valid pointer.
+#[doc(hidden)]
+pub unsafe fn handle_constructor_panic(
+    context: JSContextRef,
+    payload: Box<dyn Any + Send>,
+    exception: *mut JSValueRef,
+) {
+    let message = panic_message(&*payload);
+    match panic_policy(context) {
+        PanicPolicy::Throw => {}
+        PanicPolicy::Log => report(&format!(
+            "constructor panicked{}: {message}",
+            in_context(context)
+        )),
+        PanicPolicy::Abort => abort(context, &message),
+    }
+    *exception = make_error(context, &message);
+}
+
+/// Handles a panic caught in a class hook that can't throw, see
+/// `class_hook`.
+///
+/// # Safety
+///
+/// `context` must be a valid context.
+#[doc(hidden)]
+pub unsafe fn handle_hook_panic(context: JSContextRef, payload: Box<dyn Any + Send>) {
+    let message = panic_message(&*payload);
+    match panic_policy(context) {
+        PanicPolicy::Throw | PanicPolicy::Log => report(&format!(
+            "class hook panicked{}: {message}",
+            in_context(context)
+        )),
+        PanicPolicy::Abort => abort(context, &message),
+    }
+}
+
+/// Handles a panic caught while dropping private data during garbage
+/// collection, with the policy of the context the object was created in.
+///
+/// There is no context to throw into, so the panic is reported unless the
+/// policy is to abort.
+pub(crate) fn handle_finalizer_panic(payload: Box<dyn Any + Send>, policy: PanicPolicy) {
+    let message = panic_message(&*payload);
+    if policy == PanicPolicy::Abort {
+        report(&format!(
+            "aborting after a panic in a private data destructor: {message}"
+        ));
+        std::process::abort()
+    }
+    report(&format!("private data destructor panicked: {message}"));
+}
+
+/// Handles a panic caught while polling an async callback, returning the
+/// error that rejects its promise.
+pub(crate) fn handle_task_panic(context: JSContextRef, payload: Box<dyn Any + Send>) -> JSValueRef {
+    let message = panic_message(&*payload);
+    match panic_policy(context) {
+        PanicPolicy::Throw => {}
+        PanicPolicy::Log => report(&format!(
+            "async callback panicked{}: {message}",
+            in_context(context)
+        )),
+        PanicPolicy::Abort => abort(context, &message),
+    }
+    unsafe { make_error(context, &message) }
+}
+
+fn abort(context: JSContextRef, message: &str) -> ! {
+    report(&format!(
+        "aborting after a panic{}: {message}",
+        in_context(context)
+    ));
+    std::process::abort()
+}
+
+/// Formats the name of the context for the log messages, if it has one.
+fn in_context(context: JSContextRef) -> String {
+    crate::context_name(context)
+        .map(|name| format!(" in context {name:?}"))
+        .unwrap_or_default()
+}
+
+/// Creates an `Error` object with the given message.
+pub(crate) unsafe fn make_error(context: JSContextRef, message: &str) -> JSValueRef {
+    let message: JSString = message.into();
+    let message = JSValueMakeString(context, message.inner);
+    let mut exception: JSValueRef = std::ptr::null_mut();
+    let error = JSObjectMakeError(context, 1, &message, &mut exception);
+    if !exception.is_null() {
+        return exception;
+    }
+    error
+}
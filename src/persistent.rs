@@ -0,0 +1,143 @@
+use rusty_jsc_sys::*;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{FromJSValue, JSContext, JSException, JSObject, JSValue};
+
+/// A value that can be kept in a `Persistent` handle: `JSValue` or a
+/// generic `JSObject`.
+pub trait PersistentValue: private::Sealed {
+    #[doc(hidden)]
+    fn value_ref(&self) -> JSValueRef;
+    #[doc(hidden)]
+    fn from_value_ref(value: JSValueRef) -> Self;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+impl private::Sealed for JSValue {}
+
+impl PersistentValue for JSValue {
+    fn value_ref(&self) -> JSValueRef {
+        self.inner
+    }
+
+    fn from_value_ref(value: JSValueRef) -> Self {
+        JSValue::from(value)
+    }
+}
+
+impl private::Sealed for JSObject {}
+
+/// Only generic objects can be kept, since the other kinds of objects, such
+/// as promises, hold Rust data that a handle doesn't keep.
+impl PersistentValue for JSObject {
+    fn value_ref(&self) -> JSValueRef {
+        self.inner
+    }
+
+    fn from_value_ref(value: JSValueRef) -> Self {
+        JSObject::from(value as JSObjectRef)
+    }
+}
+
+/// A handle keeping a `JSValue` or a `JSObject` alive across calls, to be
+/// stored in Rust data structures.
+///
+/// The value is protected from garbage collection and its global context is
+/// retained as long as a handle exists. Cloning the handle protects the value
+/// once more, so each clone can be dropped independently.
+///
+/// ```rust
+/// use rusty_jsc::{JSContext, JSValue, Persistent};
+///
+/// let mut context = JSContext::default();
+/// let config = context.evaluate_script("({ verbose: true })", 1).unwrap();
+/// let config = Persistent::new(&context, &config);
+/// // ... later, in another call
+/// let config = config.to_local(&context).to_object(&context).unwrap();
+/// assert!(config.get_property(&context, "verbose").unwrap().to_bool(&context));
+/// ```
+pub struct Persistent<T: PersistentValue> {
+    inner: JSValueRef,
+    context: JSGlobalContextRef,
+    value: PhantomData<T>,
+}
+
+impl<T: PersistentValue> Persistent<T> {
+    /// Protects `value` and returns a handle to it.
+    pub fn new(context: &JSContext, value: &T) -> Self {
+        let context = unsafe { JSContextGetGlobalContext(context.inner) };
+        Self::protect(context, value.value_ref())
+    }
+
+    fn protect(context: JSGlobalContextRef, inner: JSValueRef) -> Self {
+        crate::retain_global_context(context);
+        unsafe { JSValueProtect(context, inner) };
+        Self {
+            inner,
+            context,
+            value: PhantomData,
+        }
+    }
+
+    /// Returns the value as a local value of `context`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context` isn't in the same virtual machine as the context
+    /// the handle was created in, since values can't be shared across
+    /// virtual machines.
+    pub fn to_local(&self, context: &JSContext) -> T {
+        assert_eq!(
+            unsafe { JSContextGetGroup(context.inner) },
+            unsafe { JSContextGetGroup(self.context) },
+            "a persistent value is used in another virtual machine"
+        );
+        T::from_value_ref(self.inner)
+    }
+
+    /// Returns the value as a local value of `context`, releasing the handle.
+    ///
+    /// The value can be collected once it's no longer referenced from
+    /// JavaScript or from the stack.
+    pub fn into_local(self, context: &JSContext) -> T {
+        self.to_local(context)
+    }
+
+    /// Returns the context the handle was created in.
+    pub fn context(&self) -> JSContext {
+        JSContext::from(self.context as JSContextRef)
+    }
+}
+
+impl<T: PersistentValue> Clone for Persistent<T> {
+    fn clone(&self) -> Self {
+        Self::protect(self.context, self.inner)
+    }
+}
+
+impl<T: PersistentValue> Drop for Persistent<T> {
+    fn drop(&mut self) {
+        unsafe { JSValueUnprotect(self.context, self.inner) };
+        crate::release_global_context(self.context);
+    }
+}
+
+/// Protects the converted value, e.g. to keep an argument of an `async`
+/// callback across awaits.
+impl<T: PersistentValue + FromJSValue> FromJSValue for Persistent<T> {
+    fn from_js_value(context: &JSContext, value: &JSValue) -> Result<Self, JSException> {
+        Ok(Persistent::new(context, &T::from_js_value(context, value)?))
+    }
+}
+
+impl<T: PersistentValue> fmt::Debug for Persistent<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Persistent")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
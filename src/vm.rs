@@ -0,0 +1,228 @@
+use rusty_jsc_sys::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::{Rc, Weak};
+#[cfg(jsc_execution_time_limit)]
+use std::time::Duration;
+
+use crate::JSContext;
+
+/// A JavaScript virtual machine, i.e. a JavaScriptCore context group.
+///
+/// The contexts of a virtual machine share a heap, so a value created in one
+/// context can be used in any other context of the same virtual machine:
+///
+/// - Values, objects and functions can be passed between the contexts of a
+///   virtual machine. An object keeps the global object of the context it was
+///   created in, e.g. an array created in one context isn't `instanceof` the
+///   `Array` of another context, and a function runs with the globals of its
+///   own context.
+/// - Values must never be passed between virtual machines, use structured
+///   cloning instead.
+/// - `JSString`s aren't tied to a virtual machine.
+/// - A virtual machine and its contexts stay on the thread that created them,
+///   which is where the crate keeps their state, the finalizers of their
+///   objects run and their tasks are polled. Use a `Worker` to run scripts on
+///   another thread.
+///
+/// The execution time limit and the garbage collector apply to all the
+/// contexts of the virtual machine.
+///
+/// ```rust
+/// use rusty_jsc::JSVirtualMachine;
+///
+/// let vm = JSVirtualMachine::new();
+/// vm.set_name("tenant-1");
+/// let mut first = vm.create_context();
+/// let second = vm.create_context();
+/// let value = first.evaluate_script("({ answer: 42 })", 1).unwrap();
+/// second.get_global_object().set_property(&second, "shared", value).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct JSVirtualMachine {
+    /// The finalizers and the per-context state of the crate are bound to the
+    /// thread, so the handles are `Rc`s, which can't leave it.
+    shared: Rc<Shared>,
+}
+
+/// The state of a virtual machine, shared by all the handles to its group.
+struct Shared {
+    context_group: JSContextGroupRef,
+    name: RefCell<Option<String>>,
+    /// The global contexts created in the group, keyed by their address.
+    contexts: RefCell<HashSet<usize>>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let key = self.context_group as usize;
+        // The table may already be destroyed if the thread is exiting.
+        let _ = MACHINES.try_with(|machines| {
+            let mut machines = machines.borrow_mut();
+            if machines
+                .get(&key)
+                .is_some_and(|machine| std::ptr::eq(machine.as_ptr(), self))
+            {
+                machines.remove(&key);
+            }
+        });
+        unsafe { JSContextGroupRelease(self.context_group) }
+    }
+}
+
+thread_local! {
+    /// The live virtual machines of the thread, keyed by the address of their
+    /// group, so that the handles created from a context share their state.
+    static MACHINES: RefCell<HashMap<usize, Weak<Shared>>> = RefCell::new(HashMap::new());
+}
+
+impl Shared {
+    fn new(context_group: JSContextGroupRef) -> Self {
+        Self {
+            context_group,
+            name: RefCell::new(None),
+            contexts: RefCell::default(),
+        }
+    }
+}
+
+impl JSVirtualMachine {
+    /// Creates a new virtual machine.
+    pub fn new() -> Self {
+        let context_group = unsafe { JSContextGroupCreate() };
+        let shared = Rc::new(Shared::new(context_group));
+        MACHINES.with(|machines| {
+            machines
+                .borrow_mut()
+                .insert(context_group as usize, Rc::downgrade(&shared))
+        });
+        Self { shared }
+    }
+
+    /// Returns the virtual machine of a context group.
+    pub(crate) fn from_group(context_group: JSContextGroupRef) -> Self {
+        MACHINES.with(|machines| {
+            let mut machines = machines.borrow_mut();
+            if let Some(shared) = machines
+                .get(&(context_group as usize))
+                .and_then(Weak::upgrade)
+            {
+                return Self { shared };
+            }
+            unsafe { JSContextGroupRetain(context_group) };
+            let shared = Rc::new(Shared::new(context_group));
+            machines.insert(context_group as usize, Rc::downgrade(&shared));
+            Self { shared }
+        })
+    }
+
+    pub(crate) fn group(&self) -> JSContextGroupRef {
+        self.shared.context_group
+    }
+
+    /// Records a global context created in this virtual machine.
+    pub(crate) fn add_context(&self, context: JSGlobalContextRef) {
+        self.shared.contexts.borrow_mut().insert(context as usize);
+    }
+
+    /// Creates a new context in this virtual machine.
+    pub fn create_context(&self) -> JSContext {
+        JSContext::in_virtual_machine(self, std::ptr::null_mut())
+    }
+
+    /// Sets the name of this virtual machine, shown in its `Debug` output.
+    pub fn set_name(&self, name: impl Into<String>) {
+        *self.shared.name.borrow_mut() = Some(name.into());
+    }
+
+    /// Returns the name of this virtual machine.
+    pub fn name(&self) -> Option<String> {
+        self.shared.name.borrow().clone()
+    }
+
+    /// Limits the time a script runs in the contexts of this virtual machine.
+    ///
+    /// A script running longer is terminated, with an uncatchable exception.
+    /// The limit applies to each call into JavaScript from Rust.
+    ///
+    /// This uses a private API of JavaScriptCore, which JavaScriptCoreGTK
+    /// exports but the macOS framework doesn't make public: it's only
+    /// available on macOS with the `private-api` feature.
+    #[cfg(jsc_execution_time_limit)]
+    pub fn set_execution_time_limit(&self, limit: Duration) {
+        unsafe {
+            JSContextGroupSetExecutionTimeLimit(
+                self.shared.context_group,
+                limit.as_secs_f64(),
+                None,
+                std::ptr::null_mut(),
+            )
+        }
+    }
+
+    /// Removes the execution time limit of this virtual machine.
+    #[cfg(jsc_execution_time_limit)]
+    pub fn clear_execution_time_limit(&self) {
+        unsafe { JSContextGroupClearExecutionTimeLimit(self.shared.context_group) }
+    }
+
+    /// Requests a garbage collection of the heap shared by the contexts of
+    /// this virtual machine.
+    ///
+    /// The heap can only be reached through a context, so this does nothing
+    /// when no context created by this virtual machine is alive.
+    pub fn garbage_collect(&self) {
+        // The context is retained during the collection, since the
+        // finalizers can drop contexts, so the set isn't borrowed either.
+        let context = self
+            .shared
+            .contexts
+            .borrow()
+            .iter()
+            .next()
+            .map(|&context| unsafe { JSGlobalContextRetain(context as JSGlobalContextRef) });
+        if let Some(context) = context {
+            unsafe {
+                JSGarbageCollect(context);
+                JSGlobalContextRelease(context);
+            }
+        }
+    }
+}
+
+impl Default for JSVirtualMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for JSVirtualMachine {
+    fn eq(&self, other: &Self) -> bool {
+        self.shared.context_group == other.shared.context_group
+    }
+}
+
+impl Eq for JSVirtualMachine {}
+
+impl fmt::Debug for JSVirtualMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JSVirtualMachine")
+            .field("name", &self.name())
+            .field("group", &self.shared.context_group)
+            .finish()
+    }
+}
+
+/// Forgets a global context whose last handle is dropped.
+pub(crate) fn forget_context(context: JSGlobalContextRef) {
+    let group = unsafe { JSContextGetGroup(context) } as usize;
+    // The table may already be destroyed if the thread is exiting.
+    let machine = MACHINES
+        .try_with(|machines| machines.borrow().get(&group).and_then(Weak::upgrade))
+        .ok()
+        .flatten();
+    if let Some(shared) = machine {
+        shared.contexts.borrow_mut().remove(&(context as usize));
+    }
+}
@@ -0,0 +1,194 @@
+//! Weak handles and finalization callbacks.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{
+    convert::conversion_error, JSClass, JSContext, JSException, JSObject, JSObjectGeneric,
+    JSObjectGenericClass, JSValue, JSWeakMap, JSWeakRef, Persistent,
+};
+
+/// A handle to an object that doesn't keep it alive, backed by a JavaScript
+/// `WeakRef`.
+///
+/// Unlike a `JSWeakRef`, the handle can be kept in Rust data structures
+/// across calls.
+#[derive(Debug, Clone)]
+pub struct WeakJSObject {
+    weak_ref: Persistent<JSObject>,
+}
+
+impl WeakJSObject {
+    /// Creates a weak handle to an object.
+    pub fn new<T>(context: &JSContext, target: &JSObject<T>) -> Result<Self, JSException> {
+        let weak_ref = JSWeakRef::new(context, target)?;
+        Ok(Self {
+            weak_ref: Persistent::new(context, weak_ref.as_object()),
+        })
+    }
+
+    /// Returns the object, or `None` if it has been garbage collected.
+    pub fn upgrade(&self, context: &JSContext) -> Result<Option<JSObject>, JSException> {
+        JSWeakRef::from_object(context, self.weak_ref.to_local(context))?.deref(context)
+    }
+}
+
+thread_local! {
+    /// The class of the tokens, whose private data is dropped when they are
+    /// collected.
+    static FINALIZER_CLASS: JSClass = JSClass::create("FinalizationToken", None);
+}
+
+/// The private data of a token, queueing the held value of a registration
+/// when it's dropped by the finalizer of the token class.
+struct Finalizer(Option<Box<dyn FnOnce()>>);
+
+impl Drop for Finalizer {
+    fn drop(&mut self) {
+        if let Some(queue) = self.0.take() {
+            queue();
+        }
+    }
+}
+
+/// Calls a Rust closure after registered objects are garbage collected, like
+/// a JavaScript `FinalizationRegistry`.
+///
+/// Each registered object gets a token object that only the object
+/// references, through a `WeakMap`, so the token is collected along with the
+/// object and the finalizer of its class queues the held value in the
+/// registry.
+///
+/// The callbacks run when `cleanup` is called, outside of the garbage
+/// collection, e.g. along with `JSContext::run_pending_tasks` in an event
+/// loop. Like in JavaScript, there is no guarantee that the callback runs
+/// for an object: the queued values are dropped with the registry.
+///
+/// ```rust
+/// use rusty_jsc::{FinalizationRegistry, JSContext, JSObject, JSObjectGeneric};
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let mut context = JSContext::default();
+/// let collected = Rc::new(RefCell::new(vec![]));
+/// let registry = FinalizationRegistry::new(&context, {
+///     let collected = collected.clone();
+///     move |id: u32| collected.borrow_mut().push(id)
+/// })
+/// .unwrap();
+///
+/// let unregistered = JSObject::<JSObjectGeneric>::new(&context);
+/// registry.register(&context, &unregistered, 0).unwrap();
+/// assert!(registry.unregister(&context, &unregistered).unwrap());
+/// drop(unregistered);
+/// for id in 1..=1000 {
+///     let object = JSObject::<JSObjectGeneric>::new(&context);
+///     registry.register(&context, &object, id).unwrap();
+/// }
+///
+/// // The garbage collection isn't deterministic, so garbage is created until
+/// // some of the objects are collected.
+/// for _ in 0..100 {
+///     context
+///         .evaluate_script("for (let i = 0; i < 100000; i++) ({})", 1)
+///         .unwrap();
+///     context.virtual_machine().garbage_collect();
+///     if registry.cleanup() > 0 {
+///         break;
+///     }
+/// }
+/// assert!(!collected.borrow().is_empty());
+/// assert!(!collected.borrow().contains(&0));
+/// ```
+pub struct FinalizationRegistry<H> {
+    tokens: Persistent<JSObject>,
+    callback: Box<dyn Fn(H)>,
+    /// The held values of the collected objects, waiting for `cleanup`. The
+    /// tokens only hold a weak reference, so that the values collected after
+    /// the registry is dropped are dropped as well.
+    queue: Rc<RefCell<Vec<H>>>,
+}
+
+impl<H: 'static> FinalizationRegistry<H> {
+    /// Creates a registry calling `callback` with the held value of each
+    /// collected object.
+    pub fn new(context: &JSContext, callback: impl Fn(H) + 'static) -> Result<Self, JSException> {
+        let tokens = JSWeakMap::new(context)?;
+        Ok(Self {
+            tokens: Persistent::new(context, tokens.as_object()),
+            callback: Box::new(callback),
+            queue: Rc::default(),
+        })
+    }
+
+    /// Registers an object, calling the callback with `held` once it's
+    /// collected. An object can be registered several times.
+    pub fn register<T>(
+        &self,
+        context: &JSContext,
+        target: &JSObject<T>,
+        held: H,
+    ) -> Result<(), JSException> {
+        let mut token = FINALIZER_CLASS.with(|class| class.make_object(context));
+        let queue = Rc::downgrade(&self.queue);
+        let finalizer = Finalizer(Some(Box::new(move || {
+            if let Some(queue) = queue.upgrade() {
+                queue.borrow_mut().push(held);
+            }
+        })));
+        if let Err(mut finalizer) = token.set_private_data(finalizer) {
+            finalizer.0 = None;
+            return Err(conversion_error(context, "can't attach a finalizer"));
+        }
+
+        let tokens = self.tokens(context)?;
+        let registered = tokens.get(context, target)?;
+        let registered = if registered.is_undefined(context) {
+            let registered = JSObject::<JSObjectGeneric>::new_array(context, &[])?;
+            tokens.set(context, target, &registered)?;
+            registered
+        } else {
+            registered.to_object(context)?
+        };
+        registered.call_method::<()>(context, "push", (JSValue::from(token),))
+    }
+
+    /// Unregisters an object, returning whether it was registered. The
+    /// callback won't be called for it.
+    pub fn unregister<T>(
+        &self,
+        context: &JSContext,
+        target: &JSObject<T>,
+    ) -> Result<bool, JSException> {
+        let tokens = self.tokens(context)?;
+        let registered = tokens.get(context, target)?;
+        if registered.is_undefined(context) {
+            return Ok(false);
+        }
+        for token in registered.to_object(context)?.array_iter(context)? {
+            let mut token =
+                JSObject::<JSObjectGenericClass>::from(token?.to_object(context)?.inner);
+            if let Some(mut finalizer) = token.take_private_data::<Finalizer>() {
+                finalizer.0 = None;
+            }
+        }
+        tokens.delete(context, target)
+    }
+
+    /// Calls the callback for the objects collected since the last call,
+    /// returning how many there were.
+    pub fn cleanup(&self) -> usize {
+        // The queue isn't borrowed while the callbacks run, since they can
+        // trigger a garbage collection.
+        let held = std::mem::take(&mut *self.queue.borrow_mut());
+        let count = held.len();
+        for held in held {
+            (self.callback)(held);
+        }
+        count
+    }
+
+    fn tokens(&self, context: &JSContext) -> Result<JSWeakMap, JSException> {
+        JSWeakMap::from_object(context, self.tokens.to_local(context))
+    }
+}
@@ -0,0 +1,454 @@
+//! Workers running scripts in their own virtual machine and thread.
+
+use rusty_jsc_sys::*;
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{
+    convert::conversion_error, panic::settle_callback, JSClass, JSContext, JSException, JSObject,
+    JSObjectGeneric, JSObjectGenericClass, JSValue, JSValueKind, SerializedValue, StructuredClone,
+};
+
+/// How often a running script checks if its worker was terminated.
+const TERMINATION_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+enum Command {
+    Message(SerializedValue),
+    Terminate,
+}
+
+/// An event sent by a worker to its parent.
+#[derive(Debug, Clone)]
+pub enum WorkerEvent {
+    /// A message posted by the worker with `postMessage`.
+    Message(SerializedValue),
+    /// An uncaught exception in the worker, with its description, see
+    /// `JSException::describe`, and the thrown value if it could be cloned.
+    Error {
+        message: String,
+        error: Option<SerializedValue>,
+    },
+}
+
+/// A script running in a fresh virtual machine on its own thread, like a
+/// Web Worker.
+///
+/// The worker and its parent exchange messages with `postMessage`, which
+/// copies them with `StructuredClone`. In the worker, `postMessage` is a
+/// global function and the messages of the parent are delivered to the
+/// global `onmessage` function, as `{ data }` events. Uncaught exceptions of
+/// the worker, and the rejections of the promises returned by its script or
+/// by `onmessage`, are forwarded to the parent as `WorkerEvent::Error`s.
+///
+/// ```rust,no_run
+/// use rusty_jsc::{JSContext, Worker, WorkerEvent};
+///
+/// let mut context = JSContext::default();
+/// let worker = Worker::new("onmessage = (event) => postMessage(event.data * 2);");
+/// let message = context.evaluate_script("21", 1).unwrap();
+/// worker.post_message(&context, &message).unwrap();
+/// if let Some(WorkerEvent::Message(answer)) = worker.recv() {
+///     let answer = answer.deserialize(&context).unwrap();
+///     assert_eq!(answer.to_number(&context).unwrap(), 42.0);
+/// }
+/// ```
+pub struct Worker {
+    commands: Sender<Command>,
+    events: Mutex<Receiver<WorkerEvent>>,
+    terminated: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Starts a worker running `script`.
+    pub fn new(script: impl Into<String>) -> Self {
+        let script = script.into();
+        let (commands, command_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+        let terminated = Arc::new(AtomicBool::new(false));
+        let worker_terminated = terminated.clone();
+        let thread = thread::spawn(move || {
+            run_worker(script, command_receiver, event_sender, worker_terminated)
+        });
+        Self {
+            commands,
+            events: Mutex::new(events),
+            terminated,
+            thread: Some(thread),
+        }
+    }
+
+    /// Posts a message to the worker, delivered to its `onmessage` function.
+    pub fn post_message(&self, context: &JSContext, message: &JSValue) -> Result<(), JSException> {
+        let message = StructuredClone::new().serialize(context, message)?;
+        self.commands
+            .send(Command::Message(message))
+            .map_err(|_| conversion_error(context, "the worker has terminated"))
+    }
+
+    /// Returns the next event of the worker if there is one.
+    pub fn try_recv(&self) -> Option<WorkerEvent> {
+        self.events.lock().unwrap().try_recv().ok()
+    }
+
+    /// Waits for the next event of the worker, returning `None` once the
+    /// worker has terminated.
+    pub fn recv(&self) -> Option<WorkerEvent> {
+        self.events.lock().unwrap().recv().ok()
+    }
+
+    /// Waits for the next event of the worker, for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<WorkerEvent> {
+        self.events.lock().unwrap().recv_timeout(timeout).ok()
+    }
+
+    /// Stops the worker, interrupting its running script.
+    ///
+    /// A running script is interrupted with the execution time limit of its
+    /// virtual machine, so on macOS without the `private-api` feature, see
+    /// `JSVirtualMachine::set_execution_time_limit`, the worker stops once
+    /// its script returns.
+    pub fn terminate(&self) {
+        self.terminated.store(true, Ordering::Release);
+        let _ = self.commands.send(Command::Terminate);
+    }
+
+    /// Checks if the worker thread has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Creates a JavaScript object for the worker, with `postMessage` and
+    /// `terminate` methods, to expose the worker to the scripts of the parent
+    /// context. Its `onmessage` and `onerror` functions are called by
+    /// `dispatch_events`.
+    pub fn to_js_object(&self, context: &JSContext) -> Result<JSObject, JSException> {
+        let mut object = WORKER_CLASS.with(|class| class.make_object(context));
+        object
+            .set_private_data(WorkerHandle {
+                commands: self.commands.clone(),
+                terminated: self.terminated.clone(),
+            })
+            .map_err(|_| conversion_error(context, "can't attach the worker"))?;
+        let mut object = JSObject::<JSObjectGeneric>::from(object.inner);
+        let post_message = JSValue::callback(
+            context,
+            Some(post_message_to_worker as unsafe extern "C" fn(_, _, _, _, _, _) -> _),
+        );
+        object.set_property(context, "postMessage", post_message)?;
+        let terminate = JSValue::callback(
+            context,
+            Some(terminate_worker as unsafe extern "C" fn(_, _, _, _, _, _) -> _),
+        );
+        object.set_property(context, "terminate", terminate)?;
+        Ok(object)
+    }
+
+    /// Delivers the pending events of the worker to the `onmessage` and
+    /// `onerror` functions of `target`, usually the object created by
+    /// `to_js_object`, and returns the number of events delivered.
+    pub fn dispatch_events<T>(
+        &self,
+        context: &JSContext,
+        target: &JSObject<T>,
+    ) -> Result<usize, JSException> {
+        let mut delivered = 0;
+        while let Some(event) = self.try_recv() {
+            let (handler, event) = match event {
+                WorkerEvent::Message(message) => (
+                    "onmessage",
+                    message_event(context, message.deserialize(context)?)?,
+                ),
+                WorkerEvent::Error { message, error } => {
+                    let mut event = JSObject::<JSObjectGeneric>::new(context);
+                    event.set_property(context, "message", JSValue::string(context, message))?;
+                    if let Some(error) = error {
+                        event.set_property(context, "error", error.deserialize(context)?)?;
+                    }
+                    ("onerror", event)
+                }
+            };
+            let handler = target.get_property(context, handler)?;
+            if handler.is_object(context) {
+                handler.to_object(context)?.call::<()>(
+                    context,
+                    JSValue::from(target.inner),
+                    (event,),
+                )?;
+            }
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.terminate();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Creates the `{ data }` event of a message.
+fn message_event(context: &JSContext, data: JSValue) -> Result<JSObject, JSException> {
+    let mut event = JSObject::<JSObjectGeneric>::new(context);
+    event.set_property(context, "data", data)?;
+    Ok(event)
+}
+
+/// The private data of the objects created by `Worker::to_js_object`.
+struct WorkerHandle {
+    commands: Sender<Command>,
+    terminated: Arc<AtomicBool>,
+}
+
+thread_local! {
+    static WORKER_CLASS: JSClass = JSClass::create("Worker", None);
+
+    /// The channel of the events of the worker running on this thread, with
+    /// its termination flag.
+    static WORKER_EVENTS: RefCell<Option<(Sender<WorkerEvent>, Arc<AtomicBool>)>> =
+        const { RefCell::new(None) };
+}
+
+fn run_worker(
+    script: String,
+    commands: Receiver<Command>,
+    events: Sender<WorkerEvent>,
+    terminated: Arc<AtomicBool>,
+) {
+    let mut context = JSContext::default();
+    #[cfg(jsc_execution_time_limit)]
+    unsafe {
+        JSContextGroupSetExecutionTimeLimit(
+            context.virtual_machine().group(),
+            TERMINATION_CHECK_INTERVAL.as_secs_f64(),
+            Some(should_terminate),
+            Arc::as_ptr(&terminated) as *mut _,
+        );
+    }
+    WORKER_EVENTS.with(|sender| *sender.borrow_mut() = Some((events.clone(), terminated.clone())));
+    let post_message = JSValue::callback(
+        &context,
+        Some(post_message_to_parent as unsafe extern "C" fn(_, _, _, _, _, _) -> _),
+    );
+    let mut global = context.get_global_object();
+    if global
+        .set_property(&context, "postMessage", post_message)
+        .is_err()
+    {
+        return;
+    }
+
+    match context.evaluate_script(&script, 1) {
+        Ok(value) => report_rejection(&context, &events, &terminated, &value),
+        Err(exception) => report_error(&context, &events, &terminated, exception),
+    }
+    loop {
+        let pending = context.run_pending_tasks();
+        let command = if pending > 0 {
+            match commands.recv_timeout(TERMINATION_CHECK_INTERVAL) {
+                Ok(command) => command,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match commands.recv() {
+                Ok(command) => command,
+                Err(_) => break,
+            }
+        };
+        let message = match command {
+            Command::Message(message) => message,
+            Command::Terminate => break,
+        };
+        if terminated.load(Ordering::Acquire) {
+            break;
+        }
+        match deliver_message(&context, &message) {
+            Ok(value) => report_rejection(&context, &events, &terminated, &value),
+            Err(exception) => report_error(&context, &events, &terminated, exception),
+        }
+    }
+    WORKER_EVENTS.with(|sender| sender.borrow_mut().take());
+}
+
+/// Calls the global `onmessage` function of the worker with a message,
+/// returning the result of the handler.
+fn deliver_message(context: &JSContext, message: &SerializedValue) -> Result<JSValue, JSException> {
+    let data = message.deserialize(context)?;
+    let handler = context
+        .get_global_object()
+        .get_property(context, "onmessage")?;
+    if !handler.is_object(context) {
+        return Ok(JSValue::undefined(context));
+    }
+    let event = message_event(context, data)?;
+    handler.to_object(context)?.call(context, (), (event,))
+}
+
+/// Reports the rejection of `value` as an error if it's a promise, e.g. the
+/// result of an `async` handler.
+fn report_rejection(
+    context: &JSContext,
+    events: &Sender<WorkerEvent>,
+    terminated: &AtomicBool,
+    value: &JSValue,
+) {
+    if value.kind(context) != JSValueKind::Promise {
+        return;
+    }
+    let on_rejected = JSValue::callback(
+        context,
+        Some(report_rejected_promise as unsafe extern "C" fn(_, _, _, _, _, _) -> _),
+    );
+    let result = value.to_object(context).and_then(|promise| {
+        promise.call_method::<()>(context, "then", (JSValue::undefined(context), on_rejected))
+    });
+    if let Err(exception) = result {
+        report_error(context, events, terminated, exception);
+    }
+}
+
+fn report_error(
+    context: &JSContext,
+    events: &Sender<WorkerEvent>,
+    terminated: &AtomicBool,
+    exception: JSException,
+) {
+    // The exception of a terminated script only reports the termination.
+    if terminated.load(Ordering::Acquire) {
+        return;
+    }
+    let error = StructuredClone::new()
+        .serialize(context, exception.value())
+        .ok();
+    let _ = events.send(WorkerEvent::Error {
+        message: exception.describe(context),
+        error,
+    });
+}
+
+#[cfg(jsc_execution_time_limit)]
+unsafe extern "C" fn should_terminate(
+    _ctx: JSContextRef,
+    terminated: *mut std::os::raw::c_void,
+) -> bool {
+    (*(terminated as *const AtomicBool)).load(Ordering::Acquire)
+}
+
+/// The rejection handler of the promises returned by the worker script and
+/// its `onmessage` handler.
+unsafe extern "C" fn report_rejected_promise(
+    ctx: JSContextRef,
+    _function: JSObjectRef,
+    _this: JSObjectRef,
+    argument_count: size_t,
+    arguments: *const JSValueRef,
+    exception: *mut JSValueRef,
+) -> JSValueRef {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let context = JSContext::from(ctx);
+        let reason = message_argument(&context, argument_count, arguments);
+        WORKER_EVENTS.with(|sender| {
+            if let Some((events, terminated)) = sender.borrow().as_ref() {
+                report_error(&context, events, terminated, reason.into());
+            }
+        });
+        Ok(())
+    }));
+    settle_callback(ctx, result, exception)
+}
+
+/// The `postMessage` function of the worker global object.
+unsafe extern "C" fn post_message_to_parent(
+    ctx: JSContextRef,
+    _function: JSObjectRef,
+    _this: JSObjectRef,
+    argument_count: size_t,
+    arguments: *const JSValueRef,
+    exception: *mut JSValueRef,
+) -> JSValueRef {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let context = JSContext::from(ctx);
+        let message = message_argument(&context, argument_count, arguments);
+        let message = StructuredClone::new().serialize(&context, &message)?;
+        let sent = WORKER_EVENTS.with(|sender| {
+            sender
+                .borrow()
+                .as_ref()
+                .is_some_and(|(sender, _)| sender.send(WorkerEvent::Message(message)).is_ok())
+        });
+        if !sent {
+            return Err(conversion_error(&context, "the parent has gone away"));
+        }
+        Ok(())
+    }));
+    settle_callback(ctx, result, exception)
+}
+
+/// The `postMessage` method of the objects created by `Worker::to_js_object`.
+unsafe extern "C" fn post_message_to_worker(
+    ctx: JSContextRef,
+    _function: JSObjectRef,
+    this: JSObjectRef,
+    argument_count: size_t,
+    arguments: *const JSValueRef,
+    exception: *mut JSValueRef,
+) -> JSValueRef {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let context = JSContext::from(ctx);
+        let this = JSObject::<JSObjectGenericClass>::from(this);
+        let Some(handle) = this.private_data::<WorkerHandle>() else {
+            return Err(conversion_error(
+                &context,
+                "postMessage called on a non-worker",
+            ));
+        };
+        let message = message_argument(&context, argument_count, arguments);
+        let message = StructuredClone::new().serialize(&context, &message)?;
+        handle
+            .commands
+            .send(Command::Message(message))
+            .map_err(|_| conversion_error(&context, "the worker has terminated"))
+    }));
+    settle_callback(ctx, result, exception)
+}
+
+/// The `terminate` method of the objects created by `Worker::to_js_object`.
+unsafe extern "C" fn terminate_worker(
+    ctx: JSContextRef,
+    _function: JSObjectRef,
+    this: JSObjectRef,
+    _argument_count: size_t,
+    _arguments: *const JSValueRef,
+    exception: *mut JSValueRef,
+) -> JSValueRef {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let this = JSObject::<JSObjectGenericClass>::from(this);
+        if let Some(handle) = this.private_data::<WorkerHandle>() {
+            handle.terminated.store(true, Ordering::Release);
+            let _ = handle.commands.send(Command::Terminate);
+        }
+        Ok(())
+    }));
+    settle_callback(ctx, result, exception)
+}
+
+unsafe fn message_argument(
+    context: &JSContext,
+    argument_count: size_t,
+    arguments: *const JSValueRef,
+) -> JSValue {
+    if argument_count == 0 {
+        return JSValue::undefined(context);
+    }
+    JSValue::from(*arguments)
+}
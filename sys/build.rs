@@ -1,9 +1,58 @@
 #[cfg(target_os = "macos")]
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(jsc_bigint_api)");
+    println!("cargo:rustc-check-cfg=cfg(jsc_execution_time_limit)");
     println!("cargo:rustc-link-lib=framework=JavaScriptCore");
+    // The execution time limit is a private API of the framework, so it's
+    // only declared when the `private-api` feature asks for it.
+    if std::env::var_os("CARGO_FEATURE_PRIVATE_API").is_some() {
+        println!("cargo:rustc-cfg=jsc_execution_time_limit");
+        println!("cargo:execution_time_limit=1");
+    }
+    // The `JSBigInt*` functions were added in macOS 15, so they are only
+    // used when the deployment target, or the build machine if there is
+    // none, is recent enough.
+    if macos_version().is_some_and(|version| version >= (15, 0)) {
+        println!("cargo:rustc-cfg=jsc_bigint_api");
+        println!("cargo:bigint_api=1");
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn macos_version() -> Option<(u32, u32)> {
+    println!("cargo:rerun-if-env-changed=MACOSX_DEPLOYMENT_TARGET");
+    let version = match std::env::var("MACOSX_DEPLOYMENT_TARGET") {
+        Ok(version) => version,
+        Err(_) => {
+            let output = std::process::Command::new("sw_vers")
+                .arg("-productVersion")
+                .output()
+                .ok()?;
+            String::from_utf8(output.stdout).ok()?
+        }
+    };
+    Some(parse_version(version.trim()))
 }
 
 #[cfg(target_os = "linux")]
 fn main() {
-    pkg_config::probe_library("javascriptcoregtk-4.1").unwrap();
+    println!("cargo:rustc-check-cfg=cfg(jsc_bigint_api)");
+    println!("cargo:rustc-check-cfg=cfg(jsc_execution_time_limit)");
+    // JavaScriptCoreGTK exports the execution time limit of its private
+    // headers.
+    println!("cargo:rustc-cfg=jsc_execution_time_limit");
+    println!("cargo:execution_time_limit=1");
+    let library = pkg_config::probe_library("javascriptcoregtk-4.1").unwrap();
+    // The `JSBigInt*` functions were added in JavaScriptCoreGTK 2.46. The
+    // `bigint_api` metadata lets dependent crates enable the same cfg.
+    if parse_version(&library.version) >= (2, 46) {
+        println!("cargo:rustc-cfg=jsc_bigint_api");
+        println!("cargo:bigint_api=1");
+    }
+}
+
+/// Parses the major and minor numbers of a version.
+fn parse_version(version: &str) -> (u32, u32) {
+    let mut parts = version.split('.').map(|part| part.parse::<u32>().unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
 }
@@ -92,6 +92,7 @@ pub const JSType_kJSTypeNumber: JSType = 3;
 pub const JSType_kJSTypeString: JSType = 4;
 pub const JSType_kJSTypeObject: JSType = 5;
 pub const JSType_kJSTypeSymbol: JSType = 6;
+pub const JSType_kJSTypeBigInt: JSType = 7;
 
 pub type JSType = ::std::os::raw::c_uint;
 pub const JSTypedArrayType_kJSTypedArrayTypeInt8Array: JSTypedArrayType = 0;
@@ -172,6 +173,46 @@ extern "C" {
     pub fn JSValueProtect(ctx: JSContextRef, value: JSValueRef);
     pub fn JSValueUnprotect(ctx: JSContextRef, value: JSValueRef);
 }
+// The BigInt API, available since JavaScriptCoreGTK 2.46. The build script
+// sets the `jsc_bigint_api` cfg when the linked library provides it.
+#[cfg(jsc_bigint_api)]
+extern "C" {
+    pub fn JSBigIntCreateWithDouble(
+        ctx: JSContextRef,
+        value: f64,
+        exception: *mut JSValueRef,
+    ) -> JSValueRef;
+    pub fn JSBigIntCreateWithInt64(
+        ctx: JSContextRef,
+        integer: i64,
+        exception: *mut JSValueRef,
+    ) -> JSValueRef;
+    pub fn JSBigIntCreateWithUInt64(
+        ctx: JSContextRef,
+        integer: u64,
+        exception: *mut JSValueRef,
+    ) -> JSValueRef;
+    pub fn JSBigIntCreateWithString(
+        ctx: JSContextRef,
+        string: JSStringRef,
+        exception: *mut JSValueRef,
+    ) -> JSValueRef;
+    pub fn JSValueIsBigInt(ctx: JSContextRef, value: JSValueRef) -> bool;
+    pub fn JSValueToInt32(ctx: JSContextRef, value: JSValueRef, exception: *mut JSValueRef)
+        -> i32;
+    pub fn JSValueToUInt32(
+        ctx: JSContextRef,
+        value: JSValueRef,
+        exception: *mut JSValueRef,
+    ) -> u32;
+    pub fn JSValueToInt64(ctx: JSContextRef, value: JSValueRef, exception: *mut JSValueRef)
+        -> i64;
+    pub fn JSValueToUInt64(
+        ctx: JSContextRef,
+        value: JSValueRef,
+        exception: *mut JSValueRef,
+    ) -> u64;
+}
 pub type size_t = ::std::os::raw::c_ulong;
 pub type wchar_t = ::std::os::raw::c_int;
 
@@ -1013,6 +1054,25 @@ extern "C" {
     pub fn JSGlobalContextSetName(ctx: JSGlobalContextRef, name: JSStringRef);
 }
 
+pub type JSShouldTerminateCallback = ::std::option::Option<
+    unsafe extern "C" fn(ctx: JSContextRef, context: *mut ::std::os::raw::c_void) -> bool,
+>;
+
+// From JSContextRefPrivate.h, exported by both the macOS framework and
+// JavaScriptCoreGTK. The build script sets the `jsc_execution_time_limit` cfg
+// on Linux, and on macOS with the `private-api` feature only, since the
+// framework doesn't make it public.
+#[cfg(jsc_execution_time_limit)]
+extern "C" {
+    pub fn JSContextGroupSetExecutionTimeLimit(
+        group: JSContextGroupRef,
+        limit: f64,
+        callback: JSShouldTerminateCallback,
+        context: *mut ::std::os::raw::c_void,
+    );
+    pub fn JSContextGroupClearExecutionTimeLimit(group: JSContextGroupRef);
+}
+
 pub type JSChar = ::std::os::raw::c_ushort;
 
 extern "C" {